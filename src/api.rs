@@ -0,0 +1,304 @@
+use actix_web::{ get, web, App, HttpResponse, HttpServer, Responder };
+use dotenv::dotenv;
+use near_indexer::near_primitives::types::AccountId;
+use serde::Deserialize;
+use std::env;
+use tracing::warn;
+
+use crate::db_adapters;
+use crate::db_adapters::collections::CollectionKey;
+use crate::db_adapters::holder_stats::{ HolderScope, LeaderboardOrder };
+use crate::metrics;
+
+// Default address of the API server
+const API_ADDRESS: &str = "0.0.0.0:3030";
+// Default and maximum number of items per page
+const DEFAULT_LIMIT: i64 = 50;
+const MAX_LIMIT: i64 = 500;
+
+#[get("/metrics")]
+async fn get_metrics() -> impl Responder {
+    HttpResponse::Ok()
+        .content_type("text/plain; version=0.0.4")
+        .body(metrics::render())
+}
+
+#[derive(Deserialize)]
+struct CollectionRarityQuery {
+    collection_name: Option<String>,
+    limit: Option<i64>,
+}
+
+/// Unburned tokens of the collection ranked by trait rarity, rarest first
+#[get("/collections/{contract_id}/rarity")]
+async fn get_collection_rarity(
+    pool: web::Data<mongodb::Client>,
+    contract_id: web::Path<String>,
+    query: web::Query<CollectionRarityQuery>,
+) -> impl Responder {
+
+    let contract_id = match contract_id.into_inner().parse::<AccountId>() {
+        Ok(contract_id) => contract_id,
+        Err(_) => return HttpResponse::BadRequest().body("Invalid contract_id"),
+    };
+
+    let query = query.into_inner();
+
+    let collection_id = CollectionKey {
+        contract_id,
+        collection_name: query.collection_name,
+        game_id: None,
+        rarity: None,
+    }.id();
+
+    let limit = query.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+
+    match db_adapters::trait_rarity::get_ranked_tokens(&pool, &collection_id, limit).await {
+        Ok(tokens) => {
+            let ranking: Vec<serde_json::Value> = tokens.iter()
+                .enumerate()
+                .map(|(index, token)| serde_json::json!({
+                    "rank": index + 1,
+                    "token_id": token.token_id,
+                    "score": token.trait_rarity.as_ref().map(|trait_rarity| trait_rarity.score),
+                }))
+                .collect();
+
+            HttpResponse::Ok().json(ranking)
+        },
+        Err(error) => {
+            warn!( target: crate::INDEXER, "Error reading ranked tokens from database: {:?}", &error );
+            HttpResponse::InternalServerError().finish()
+        },
+    }
+}
+
+/// Trait rarity of the token and its rank within the collection
+#[get("/tokens/{contract_id}/{token_id}/rarity")]
+async fn get_token_rarity(
+    pool: web::Data<mongodb::Client>,
+    path: web::Path<(String, String)>,
+) -> impl Responder {
+
+    let (contract_id, token_id) = path.into_inner();
+
+    let contract_id = match contract_id.parse::<AccountId>() {
+        Ok(contract_id) => contract_id,
+        Err(_) => return HttpResponse::BadRequest().body("Invalid contract_id"),
+    };
+
+    let trait_rarity = match db_adapters::tokens::get_token(&pool, contract_id, token_id).await {
+        Ok(Some(token)) => match token.trait_rarity {
+            Some(trait_rarity) => trait_rarity,
+            None => return HttpResponse::NotFound().body("Token has no traits"),
+        },
+        Ok(None) => return HttpResponse::NotFound().body("Unknown token"),
+        Err(error) => {
+            warn!( target: crate::INDEXER, "Error reading token from database: {:?}", &error );
+            return HttpResponse::InternalServerError().finish();
+        },
+    };
+
+    match db_adapters::trait_rarity::get_trait_rarity_rank(&pool, &trait_rarity).await {
+        Ok((rank, total)) => HttpResponse::Ok().json(serde_json::json!({
+            "collection_id": trait_rarity.collection_id,
+            "attributes": trait_rarity.attributes,
+            "score": trait_rarity.score,
+            "rank": rank,
+            "total": total,
+        })),
+        Err(error) => {
+            warn!( target: crate::INDEXER, "Error ranking token: {:?}", &error );
+            HttpResponse::InternalServerError().finish()
+        },
+    }
+}
+
+#[derive(Deserialize)]
+struct TokenSearchQuery {
+    q: String,
+    contract_id: Option<String>,
+    game_id: Option<String>,
+    limit: Option<i64>,
+}
+
+/// Tokens by title, description or collection name
+#[get("/tokens/search")]
+async fn search_tokens(
+    pool: web::Data<mongodb::Client>,
+    query: web::Query<TokenSearchQuery>,
+) -> impl Responder {
+
+    let query = query.into_inner();
+
+    let contract_id = match query.contract_id.map(|contract_id| contract_id.parse::<AccountId>()).transpose() {
+        Ok(contract_id) => contract_id,
+        Err(_) => return HttpResponse::BadRequest().body("Invalid contract_id"),
+    };
+
+    let limit = query.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+
+    match db_adapters::tokens::search_tokens(&pool, query.q, contract_id, query.game_id, limit).await {
+        Ok(tokens) => {
+            let results: Vec<serde_json::Value> = tokens.iter()
+                .map(|token| {
+                    let metadata = token.metadata.clone().unwrap_or_default();
+
+                    serde_json::json!({
+                        "id": token.id(),
+                        "contract_id": token.contract_id,
+                        "token_id": token.token_id,
+                        "title": metadata.title,
+                        "description": metadata.description,
+                        "collection_name": metadata.collection_name,
+                        "game_id": metadata.game_id,
+                        "current_owner": token.current_owner,
+                        "burned": token.burned,
+                    })
+                })
+                .collect();
+
+            HttpResponse::Ok().json(results)
+        },
+        Err(error) => {
+            warn!( target: crate::INDEXER, "Error searching tokens in database: {:?}", &error );
+            HttpResponse::InternalServerError().finish()
+        },
+    }
+}
+
+/// Token stored under a keccak id before versioned ids
+#[get("/tokens/legacy/{legacy_id}")]
+async fn get_token_by_legacy_id(
+    pool: web::Data<mongodb::Client>,
+    legacy_id: web::Path<String>,
+) -> impl Responder {
+
+    match db_adapters::tokens::get_token_by_legacy_id(&pool, legacy_id.into_inner()).await {
+        Ok(Some(token)) => HttpResponse::Ok().json(serde_json::json!({
+            "id": token.id(),
+            "contract_id": token.contract_id,
+            "token_id": token.token_id,
+        })),
+        Ok(None) => HttpResponse::NotFound().body("Unknown token"),
+        Err(error) => {
+            warn!( target: crate::INDEXER, "Error reading token from database: {:?}", &error );
+            HttpResponse::InternalServerError().finish()
+        },
+    }
+}
+
+/// Items of the game held by the wallet
+#[get("/games/{game_id}/inventories/{owner}")]
+async fn get_game_inventory(
+    pool: web::Data<mongodb::Client>,
+    path: web::Path<(String, String)>,
+) -> impl Responder {
+
+    let (game_id, owner) = path.into_inner();
+
+    match db_adapters::game_inventories::get_inventory(&pool, game_id, owner).await {
+        Ok(Some(inventory)) => HttpResponse::Ok().json(inventory.items),
+        Ok(None) => HttpResponse::Ok().json(Vec::<serde_json::Value>::new()),
+        Err(error) => {
+            warn!( target: crate::INDEXER, "Error reading inventory from database: {:?}", &error );
+            HttpResponse::InternalServerError().finish()
+        },
+    }
+}
+
+#[derive(Deserialize)]
+struct LeaderboardQuery {
+    /// `count` (default) or `rarity`
+    order: Option<String>,
+    limit: Option<i64>,
+}
+
+/// Top holders of a contract or a game
+#[get("/leaderboards/{scope}/{scope_id}")]
+async fn get_leaderboard(
+    pool: web::Data<mongodb::Client>,
+    path: web::Path<(String, String)>,
+    query: web::Query<LeaderboardQuery>,
+) -> impl Responder {
+
+    let (scope, scope_id) = path.into_inner();
+
+    let scope = match scope.as_str() {
+        "contract" => HolderScope::Contract,
+        "game" => HolderScope::Game,
+        _ => return HttpResponse::BadRequest().body("Scope must be contract or game"),
+    };
+
+    let order = match query.order.as_deref() {
+        None | Some("count") => LeaderboardOrder::TokenCount,
+        Some("rarity") => LeaderboardOrder::RarityScore,
+        _ => return HttpResponse::BadRequest().body("Order must be count or rarity"),
+    };
+
+    let limit = query.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+
+    match db_adapters::holder_stats::get_leaderboard(&pool, scope, scope_id, order, limit).await {
+        Ok(holders) => {
+            let leaderboard: Vec<serde_json::Value> = holders.iter()
+                .enumerate()
+                .map(|(index, holder)| serde_json::json!({
+                    "rank": index + 1,
+                    "owner": holder.owner,
+                    "token_count": holder.token_count,
+                    "rarity_score": holder.rarity_score,
+                }))
+                .collect();
+
+            HttpResponse::Ok().json(leaderboard)
+        },
+        Err(error) => {
+            warn!( target: crate::INDEXER, "Error reading leaderboard from database: {:?}", &error );
+            HttpResponse::InternalServerError().finish()
+        },
+    }
+}
+
+#[get("/wallets/{owner}/stats")]
+async fn get_wallet_stats(
+    pool: web::Data<mongodb::Client>,
+    owner: web::Path<String>,
+) -> impl Responder {
+
+    match db_adapters::holder_stats::get_wallet_stats(&pool, owner.into_inner()).await {
+        Ok(Some(stats)) => HttpResponse::Ok().json(stats),
+        Ok(None) => HttpResponse::NotFound().body("Unknown wallet"),
+        Err(error) => {
+            warn!( target: crate::INDEXER, "Error reading wallet stats from database: {:?}", &error );
+            HttpResponse::InternalServerError().finish()
+        },
+    }
+}
+
+/// Get API address from .env or use the default
+fn get_api_address() -> String {
+    dotenv().ok();
+
+    env::var("API_ADDRESS").unwrap_or(API_ADDRESS.to_string())
+}
+
+pub(crate) fn start_server(pool: mongodb::Client) -> std::io::Result<actix_web::dev::Server> {
+    let pool = web::Data::new(pool);
+
+    let server = HttpServer::new(move || {
+        App::new()
+            .app_data(pool.clone())
+            .service(get_metrics)
+            .service(get_collection_rarity)
+            .service(get_token_rarity)
+            .service(get_game_inventory)
+            .service(get_token_by_legacy_id)
+            .service(search_tokens)
+            .service(get_leaderboard)
+            .service(get_wallet_stats)
+    })
+    .bind(get_api_address())?
+    .run();
+
+    Ok(server)
+}
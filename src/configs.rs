@@ -1,6 +1,10 @@
 use clap::Parser;
+use dotenv::dotenv;
+use serde::Deserialize;
+use std::collections::{ HashMap, HashSet };
+use std::env;
 
-use near_indexer::near_primitives::types::Gas;
+use near_indexer::near_primitives::types::{ AccountId, Gas };
 
 /// NEAR Indexer Example
 /// Watches for stream of blocks from the chain
@@ -21,6 +25,39 @@ pub(crate) enum SubCommand {
     Run,
     /// Initialize necessary configs
     Init(InitConfigArgs),
+    /// Rewrite owner strings in token ownership histories as structured entries
+    MigrateHistory,
+    /// Reparse token metadata stored as plain strings into typed fields
+    MigrateMetadata,
+    /// Report tokens whose current owner disagrees with the wallet collection
+    CheckConsistency,
+    /// Move tokens from keccak ids to versioned ids and update wallets and the game backend
+    MigrateIds,
+    /// Credit unburned tokens to the leaderboard entries of their holders where the credit is missing or stale
+    BackfillLeaderboards,
+    /// Print the holders of a contract's tokens at a block height, from indexed data only
+    Snapshot(SnapshotArgs),
+}
+
+#[derive(Parser, Debug)]
+pub(crate) struct SnapshotArgs {
+    /// Contract of the tokens
+    #[clap(long)]
+    pub contract: AccountId,
+    /// Only tokens with this `collection_name`
+    #[clap(long)]
+    pub collection: Option<String>,
+    /// Block height of the snapshot, changes at this height included
+    #[clap(long)]
+    pub height: u64,
+    #[clap(long, arg_enum, default_value = "json")]
+    pub format: SnapshotFormat,
+}
+
+#[derive(clap::ArgEnum, Clone, Debug)]
+pub(crate) enum SnapshotFormat {
+    Csv,
+    Json,
 }
 
 #[derive(Parser, Debug)]
@@ -80,3 +117,179 @@ impl From<InitConfigArgs> for near_indexer::InitConfigArgs {
         }
     }
 }
+
+/// Contracts and event schemas the indexer watches.
+/// Loaded from the JSON file at `WATCH_CONFIG_PATH`, falls back to the built-in contracts when unset
+#[derive(Deserialize, Debug, Clone)]
+pub(crate) struct WatchConfig {
+    #[serde(default = "default_nft_contracts")]
+    pub nft_contracts: Vec<AccountId>,
+    #[serde(default = "default_ft_contracts")]
+    pub ft_contracts: Vec<AccountId>,
+    /// NFT contracts that don't emit NEP-297 events. Their transfers are inferred from function calls
+    #[serde(default)]
+    pub legacy_nft_contracts: Vec<AccountId>,
+    /// NEP-393 soulbound token registries
+    #[serde(default)]
+    pub sbt_registries: Vec<AccountId>,
+    #[serde(default)]
+    pub custom_events: Vec<CustomEventSchema>,
+    /// NFT contract methods updating token metadata, called with `token_id` and a partial `metadata`
+    #[serde(default = "default_metadata_update_methods")]
+    pub metadata_update_methods: Vec<String>,
+    /// Accounts expected to mint on an NFT contract, as predecessor or signer.
+    /// Other mints are reported as anomalies, contracts without an entry accept any minter
+    #[serde(default)]
+    pub allowed_minters: HashMap<AccountId, Vec<AccountId>>,
+}
+
+/// Maps a custom NEP-297 `standard`/`event` pair to the collection its data is stored in
+#[derive(Deserialize, Debug, Clone)]
+pub(crate) struct CustomEventSchema {
+    pub standard: String,
+    pub event: String,
+    pub collection: String,
+    #[serde(default)]
+    pub fields: Vec<FieldRule>,
+    /// gg-backend endpoint notified with every stored record, relative to `SERVER_BASE_URL`
+    pub endpoint: Option<String>,
+    /// Extracted `token_id` and `metadata` fields update the token metadata
+    #[serde(default)]
+    pub metadata_update: bool,
+}
+
+/// Extracts a single field from an event data entry
+#[derive(Deserialize, Debug, Clone)]
+pub(crate) struct FieldRule {
+    pub name: String,
+    /// JSON pointer into the event data entry, ex. "/player/level"
+    pub path: String,
+    #[serde(default)]
+    pub required: bool,
+    #[serde(default)]
+    pub field_type: FieldType,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum FieldType {
+    Any,
+    String,
+    Number,
+    Bool,
+    Object,
+    Array,
+}
+
+impl Default for FieldType {
+    fn default() -> Self {
+        FieldType::Any
+    }
+}
+
+impl Default for WatchConfig {
+    fn default() -> Self {
+        Self {
+            nft_contracts: default_nft_contracts(),
+            ft_contracts: default_ft_contracts(),
+            legacy_nft_contracts: Vec::new(),
+            sbt_registries: Vec::new(),
+            custom_events: Vec::new(),
+            metadata_update_methods: default_metadata_update_methods(),
+            allowed_minters: HashMap::new(),
+        }
+    }
+}
+
+impl WatchConfig {
+    pub(crate) fn watched_contracts(&self) -> HashSet<AccountId> {
+        self.nft_contracts.iter()
+            .chain(self.ft_contracts.iter())
+            .chain(self.legacy_nft_contracts.iter())
+            .chain(self.sbt_registries.iter())
+            .cloned()
+            .collect()
+    }
+
+    pub(crate) fn is_nft_contract(&self, contract_id: &AccountId) -> bool {
+        self.nft_contracts.contains(contract_id) || self.is_legacy_contract(contract_id)
+    }
+
+    pub(crate) fn is_ft_contract(&self, contract_id: &AccountId) -> bool {
+        self.ft_contracts.contains(contract_id)
+    }
+
+    pub(crate) fn is_legacy_contract(&self, contract_id: &AccountId) -> bool {
+        self.legacy_nft_contracts.contains(contract_id)
+    }
+
+    pub(crate) fn is_allowed_minter(&self, contract_id: &AccountId, predecessor_id: &AccountId, signer_id: &AccountId) -> bool {
+        match self.allowed_minters.get(contract_id) {
+            Some(minters) => minters.contains(predecessor_id) || minters.contains(signer_id),
+            None => true,
+        }
+    }
+
+    pub(crate) fn find_custom_event_schema(&self, standard: &str, event: &str) -> Option<&CustomEventSchema> {
+        self.custom_events.iter().find(|schema| schema.standard == standard && schema.event == event)
+    }
+}
+
+fn default_nft_contracts() -> Vec<AccountId> {
+    vec![AccountId::try_from(crate::NFT_CONTRACT_ID.to_string()).unwrap()]
+}
+
+fn default_ft_contracts() -> Vec<AccountId> {
+    vec![AccountId::try_from(crate::FT_CONTRACT_ID.to_string()).unwrap()]
+}
+
+fn default_metadata_update_methods() -> Vec<String> {
+    vec!["nft_update_metadata".to_string()]
+}
+
+/// Get watch config from the file set in .env or use the defaults
+pub(crate) fn get_watch_config() -> WatchConfig {
+    dotenv().ok();
+
+    match env::var("WATCH_CONFIG_PATH") {
+        Ok(path) => {
+            let config = std::fs::read_to_string(&path).expect("Failed to read WATCH_CONFIG_PATH file");
+            serde_json::from_str(&config).expect("Invalid watch config")
+        },
+        Err(_) => WatchConfig::default(),
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn account(account_id: &str) -> AccountId {
+        account_id.parse().unwrap()
+    }
+
+    #[test]
+    fn minters_are_checked_as_predecessor_or_signer() {
+        let config: WatchConfig = serde_json::from_value(json!({
+            "allowed_minters": { "game.near": ["minter.game.near"] },
+        })).unwrap();
+
+        let contract_id = account("game.near");
+
+        assert!(config.is_allowed_minter(&contract_id, &account("minter.game.near"), &account("alice.near")));
+        assert!(config.is_allowed_minter(&contract_id, &account("proxy.near"), &account("minter.game.near")));
+        assert!(!config.is_allowed_minter(&contract_id, &account("proxy.near"), &account("alice.near")));
+    }
+
+    #[test]
+    fn contracts_without_an_entry_accept_any_minter() {
+        let config: WatchConfig = serde_json::from_value(json!({
+            "allowed_minters": { "game.near": ["minter.game.near"] },
+        })).unwrap();
+
+        assert!(config.is_allowed_minter(&account("other.near"), &account("alice.near"), &account("alice.near")));
+        assert!(WatchConfig::default().is_allowed_minter(&account("game.near"), &account("alice.near"), &account("alice.near")));
+    }
+}
@@ -1,6 +1,31 @@
 pub mod tokens;
 pub mod token_owners;
+pub mod custom_events;
+pub mod approvals;
+pub mod royalties;
+pub mod storage_registrations;
+pub mod soulbound_tokens;
+pub mod contracts;
+pub mod indexes;
+pub mod anomalies;
+pub mod collections;
+pub mod trait_rarity;
+pub mod game_inventories;
+pub mod holder_stats;
 
 // Database constants
 static WALLET_TOKENS: &str = "near_wallet_nfts";
-static TOKEN_TABLE: &str = "near_nfts";
\ No newline at end of file
+static TOKEN_TABLE: &str = "near_nfts";
+static TOKEN_APPROVALS: &str = "near_nft_approvals";
+static ROYALTY_DISTRIBUTIONS: &str = "near_nft_royalty_distributions";
+static STORAGE_REGISTRATIONS: &str = "near_ft_storage_registrations";
+static SOULBOUND_TOKENS: &str = "near_sbts";
+static SOULBOUND_BANNED_ACCOUNTS: &str = "near_sbt_banned_accounts";
+static CONTRACTS: &str = "contracts";
+static ANOMALIES: &str = "anomalies";
+static COLLECTIONS: &str = "near_nft_collections";
+static COLLECTION_HOLDERS: &str = "near_nft_collection_holders";
+static COLLECTION_TRAITS: &str = "near_nft_collection_traits";
+static GAME_INVENTORIES: &str = "near_game_inventories";
+static HOLDER_STATS: &str = "near_holder_stats";
+static WALLET_STATS: &str = "near_wallet_stats";
@@ -0,0 +1,85 @@
+use near_indexer::near_primitives::types::AccountId;
+use serde::{ Deserialize, Serialize };
+
+use tracing::info;
+
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum AnomalyKind {
+    /// Transfer of a token that was never minted in indexed state
+    UnknownToken,
+    /// Transfer whose old owner isn't the indexed owner
+    WrongOldOwner,
+    TransferAfterBurn,
+    /// Minted token without matching args while the receipt had mint args
+    MintArgsUnmatched,
+    /// Mint by an account that isn't in `allowed_minters` of the contract
+    UnexpectedMinter,
+    /// `nft_mint` call of a legacy contract whose args don't name the token or its owner, ex. auto-increment ids
+    UninferableMint,
+}
+
+impl AnomalyKind {
+    pub(crate) const ALL: [AnomalyKind; 6] = [
+        AnomalyKind::UnknownToken,
+        AnomalyKind::WrongOldOwner,
+        AnomalyKind::TransferAfterBurn,
+        AnomalyKind::MintArgsUnmatched,
+        AnomalyKind::UnexpectedMinter,
+        AnomalyKind::UninferableMint,
+    ];
+
+    pub(crate) fn as_str(&self) -> &'static str {
+        match self {
+            AnomalyKind::UnknownToken => "unknown_token",
+            AnomalyKind::WrongOldOwner => "wrong_old_owner",
+            AnomalyKind::TransferAfterBurn => "transfer_after_burn",
+            AnomalyKind::MintArgsUnmatched => "mint_args_unmatched",
+            AnomalyKind::UnexpectedMinter => "unexpected_minter",
+            AnomalyKind::UninferableMint => "uninferable_mint",
+        }
+    }
+}
+
+/// Indexed state disagreeing with an event, with the chain context to investigate it
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub(crate) struct AnomalyDB {
+    pub kind: AnomalyKind,
+    pub contract_id: AccountId,
+    pub token_id: Option<String>,
+    pub receipt_id: String,
+    pub block_height: u64,
+    /// Block timestamp in nanoseconds
+    pub block_timestamp: u64,
+    /// Value according to indexed state
+    pub expected: Option<String>,
+    /// Value according to the event
+    pub actual: Option<String>,
+    /// Event data the anomaly was detected on
+    pub details: serde_json::Value,
+}
+
+pub(crate) async fn store_anomaly(
+    pool: &mongodb::Client,
+    anomaly: AnomalyDB,
+) -> anyhow::Result<()> {
+
+    info!(
+        target: crate::INDEXER,
+        "Adding anomaly to DB: {:#?}",
+        &anomaly,
+    );
+
+    let db = pool.database(crate::DB_NAME);
+    let anomalies_collection = db.collection::<AnomalyDB>(super::ANOMALIES);
+
+    crate::await_retry_or_panic!(
+        anomalies_collection.insert_one(anomaly.clone(), None),
+        10,
+        "Anomaly was NOT added to database".to_string(),
+        &anomaly,
+    );
+
+    Ok(())
+}
@@ -0,0 +1,256 @@
+use futures::TryStreamExt;
+use mongodb::bson::{ doc, Document };
+use mongodb::options::{ FindOneAndUpdateOptions, ReturnDocument, UpdateOptions };
+use near_indexer::near_primitives::types::AccountId;
+use serde::{ Deserialize, Serialize };
+
+use tracing::info;
+use crate::utils;
+
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub(crate) struct TokenApprovalsDB {
+    _id: String,
+    pub contract_id: AccountId,
+    pub token_id: String,
+    pub owner_id: Option<String>,
+    /// Last approval id handed out, counted like near-contract-standards which starts at 1.
+    /// Used when the contract doesn't report approval ids
+    #[serde(default)]
+    pub last_approval_id: u64,
+    #[serde(default)]
+    pub approvals: Vec<ApprovalDB>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub(crate) struct ApprovalDB {
+    pub account_id: String,
+    pub approval_id: u64,
+    pub msg: Option<String>,
+}
+
+pub(crate) async fn add_approval(
+    pool: &mongodb::Client,
+    contract_id: AccountId,
+    token_id: String,
+    owner_id: String,
+    account_id: String,
+    approval_id: Option<u64>,
+    msg: Option<String>,
+) -> anyhow::Result<()> {
+
+    info!(
+        target: crate::INDEXER,
+        "Approving {} for token {} in contract {}",
+        &account_id, &token_id, &contract_id,
+    );
+
+    let db = pool.database(crate::DB_NAME);
+    let approvals_collection = db.collection::<TokenApprovalsDB>(super::TOKEN_APPROVALS);
+
+    let token_db_id = utils::get_token_db_id(&contract_id, &token_id);
+
+    // Take the next approval id and remove any previous approval of the same account
+    let approvals = {
+        let query = doc!{ "_id": token_db_id.clone() };
+        let update = doc!{
+            "$inc": { "last_approval_id": 1 },
+            "$set": { "contract_id": contract_id.to_string(), "token_id": token_id.clone(), "owner_id": owner_id.clone() },
+            "$pull": { "approvals": { "account_id": account_id.clone() } },
+        };
+        let options = FindOneAndUpdateOptions::builder().upsert(true).return_document(ReturnDocument::After).build();
+
+        crate::await_retry_or_panic!(
+            approvals_collection.find_one_and_update(query.clone(), update.clone(), options.clone()),
+            10,
+            "Approval id was NOT updated in database".to_string(),
+            (&token_id, &account_id),
+        )
+    };
+
+    let approval = ApprovalDB {
+        account_id,
+        approval_id: get_approval_id(approval_id, approvals.flatten().as_ref()),
+        msg,
+    };
+
+    let query = doc!{ "_id": token_db_id };
+    let update = doc!{ "$push": { "approvals": mongodb::bson::to_bson(&approval)? } };
+    let options = UpdateOptions::builder().upsert(true).build();
+
+    crate::await_retry_or_panic!(
+        approvals_collection.update_one(query.clone(), update.clone(), options.clone()),
+        10,
+        "Approval was NOT added to database".to_string(),
+        (&token_id, &approval),
+    );
+
+    Ok(())
+}
+
+pub(crate) async fn remove_approval(
+    pool: &mongodb::Client,
+    contract_id: AccountId,
+    token_id: String,
+    account_id: String,
+) -> anyhow::Result<()> {
+
+    info!(
+        target: crate::INDEXER,
+        "Revoking {} for token {} in contract {}",
+        &account_id, &token_id, &contract_id,
+    );
+
+    let db = pool.database(crate::DB_NAME);
+    let approvals_collection = db.collection::<TokenApprovalsDB>(super::TOKEN_APPROVALS);
+
+    let token_db_id = utils::get_token_db_id(&contract_id, &token_id);
+
+    let query = doc!{ "_id": token_db_id };
+    let update = doc!{ "$pull": { "approvals": { "account_id": account_id.clone() } } };
+
+    crate::await_retry_or_panic!(
+        approvals_collection.update_one(query.clone(), update.clone(), None),
+        10,
+        "Approval was NOT removed from database".to_string(),
+        (&token_id, &account_id),
+    );
+
+    Ok(())
+}
+
+/// Approval id reported by the contract, or else the one just taken from the token's counter
+fn get_approval_id(
+    reported_approval_id: Option<u64>,
+    approvals: Option<&TokenApprovalsDB>,
+) -> u64 {
+    reported_approval_id.unwrap_or(approvals.map(|approvals| approvals.last_approval_id).unwrap_or(1))
+}
+
+fn get_clear_approvals_update(owner_id: Option<String>) -> Document {
+    match owner_id {
+        Some(owner_id) => doc!{ "$set": { "approvals": [], "owner_id": owner_id } },
+        None => doc!{ "$set": { "approvals": [] } },
+    }
+}
+
+/// Drops every approval of the token. Used by `nft_revoke_all` and transfers, which set the new owner
+pub(crate) async fn clear_approvals(
+    pool: &mongodb::Client,
+    contract_id: AccountId,
+    token_id: String,
+    owner_id: Option<String>,
+) -> anyhow::Result<()> {
+
+    info!(
+        target: crate::INDEXER,
+        "Clearing approvals for token {} in contract {}",
+        &token_id, &contract_id,
+    );
+
+    let db = pool.database(crate::DB_NAME);
+    let approvals_collection = db.collection::<TokenApprovalsDB>(super::TOKEN_APPROVALS);
+
+    let token_db_id = utils::get_token_db_id(&contract_id, &token_id);
+
+    let query = doc!{ "_id": token_db_id };
+    let update = get_clear_approvals_update(owner_id);
+
+    crate::await_retry_or_panic!(
+        approvals_collection.update_one(query.clone(), update.clone(), None),
+        10,
+        "Approvals were NOT cleared in database".to_string(),
+        &token_id,
+    );
+
+    Ok(())
+}
+
+pub(crate) async fn get_token_approvals(
+    pool: &mongodb::Client,
+    contract_id: AccountId,
+    token_id: String,
+) -> anyhow::Result<Option<TokenApprovalsDB>> {
+
+    let db = pool.database(crate::DB_NAME);
+    let approvals_collection = db.collection::<TokenApprovalsDB>(super::TOKEN_APPROVALS);
+
+    let token_db_id = utils::get_token_db_id(&contract_id, &token_id);
+
+    let query = doc!{ "_id": token_db_id };
+
+    let approvals = crate::await_retry_or_panic!(
+        approvals_collection.find_one(query.clone(), None),
+        10,
+        "Approvals were NOT read from database".to_string(),
+        (&contract_id, &token_id),
+    );
+
+    Ok(approvals.flatten())
+}
+
+/// Approvals on every token of the owner, ex. to list the marketplaces holding a player's items
+pub(crate) async fn get_owner_approvals(
+    pool: &mongodb::Client,
+    owner_id: String,
+) -> anyhow::Result<Vec<TokenApprovalsDB>> {
+
+    let db = pool.database(crate::DB_NAME);
+    let approvals_collection = db.collection::<TokenApprovalsDB>(super::TOKEN_APPROVALS);
+
+    let query = doc!{ "owner_id": owner_id.clone(), "approvals.0": { "$exists": true } };
+
+    let cursor = crate::await_retry_or_panic!(
+        approvals_collection.find(query.clone(), None),
+        10,
+        "Approvals were NOT read from database".to_string(),
+        &owner_id,
+    );
+
+    match cursor {
+        Some(cursor) => Ok(cursor.try_collect().await?),
+        None => Ok(Vec::new()),
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn token_approvals(last_approval_id: u64) -> TokenApprovalsDB {
+        TokenApprovalsDB {
+            _id: "v1:game.near:1".to_string(),
+            contract_id: "game.near".parse().unwrap(),
+            token_id: "1".to_string(),
+            owner_id: Some("alice.near".to_string()),
+            last_approval_id,
+            approvals: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn approval_ids_count_from_1() {
+        // First approval, the counter was just created by the upsert
+        assert_eq!(get_approval_id(None, Some(&token_approvals(1))), 1);
+        assert_eq!(get_approval_id(None, None), 1);
+        assert_eq!(get_approval_id(None, Some(&token_approvals(3))), 3);
+    }
+
+    #[test]
+    fn reported_approval_ids_win() {
+        assert_eq!(get_approval_id(Some(7), Some(&token_approvals(3))), 7);
+        assert_eq!(get_approval_id(Some(0), None), 0);
+    }
+
+    #[test]
+    fn transfers_clear_approvals_for_the_new_owner() {
+        assert_eq!(
+            get_clear_approvals_update(Some("bob.near".to_string())),
+            doc!{ "$set": { "approvals": [], "owner_id": "bob.near" } },
+        );
+
+        // `nft_revoke_all` keeps the owner
+        assert_eq!(get_clear_approvals_update(None), doc!{ "$set": { "approvals": [] } });
+    }
+}
@@ -0,0 +1,302 @@
+use mongodb::bson::{ doc, Document };
+use mongodb::options::{ FindOneAndUpdateOptions, ReturnDocument, UpdateOptions };
+use near_indexer::near_primitives::types::AccountId;
+use serde::{ Deserialize, Serialize };
+
+use tracing::info;
+
+
+/// Aggregates of the tokens sharing a `collection_name` in a contract.
+/// Tokens without a collection name are counted in the contract's unnamed collection
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub(crate) struct CollectionDB {
+    _id: String,
+    pub contract_id: AccountId,
+    pub collection_name: Option<String>,
+    /// Game of the first minted token
+    pub game_id: Option<String>,
+    /// Tokens that aren't burned
+    pub supply: u64,
+    pub minted: u64,
+    pub burned: u64,
+    /// Wallets holding at least one token, see `near_nft_collection_holders`
+    pub holders: u64,
+    /// Token count per rarity, burned tokens excluded
+    #[serde(default)]
+    pub rarity_distribution: Vec<RarityCount>,
+    pub first_mint_height: u64,
+    pub last_mint_height: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub(crate) struct RarityCount {
+    pub rarity: String,
+    pub count: i64,
+}
+
+/// Number of tokens of a collection held by a wallet
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub(crate) struct CollectionHolderDB {
+    _id: String,
+    pub collection_id: String,
+    pub owner: String,
+    pub count: i64,
+}
+
+/// Collection of a token, taken from its metadata
+#[derive(Debug, Clone)]
+pub(crate) struct CollectionKey {
+    pub contract_id: AccountId,
+    pub collection_name: Option<String>,
+    pub game_id: Option<String>,
+    pub rarity: Option<String>,
+}
+
+impl CollectionKey {
+    pub(crate) fn id(&self) -> String {
+        format!("{}:{}", self.contract_id, self.collection_name.clone().unwrap_or_default())
+    }
+}
+
+
+pub(crate) async fn record_collection_mint(
+    pool: &mongodb::Client,
+    key: &CollectionKey,
+    owner: String,
+    block_height: u64,
+) -> anyhow::Result<()> {
+
+    info!(
+        target: crate::INDEXER,
+        "Adding mint to collection {}",
+        key.id(),
+    );
+
+    let db = pool.database(crate::DB_NAME);
+    let collections = db.collection::<CollectionDB>(super::COLLECTIONS);
+
+    let query = doc!{ "_id": key.id() };
+    let update = doc!{
+        "$inc": { "supply": 1i64, "minted": 1i64 },
+        "$min": { "first_mint_height": block_height as i64 },
+        "$max": { "last_mint_height": block_height as i64 },
+        "$setOnInsert": {
+            "contract_id": key.contract_id.to_string(),
+            "collection_name": key.collection_name.clone(),
+            "game_id": key.game_id.clone(),
+            "burned": 0i64,
+            "holders": 0i64,
+            "rarity_distribution": [],
+        },
+    };
+    let options = UpdateOptions::builder().upsert(true).build();
+
+    crate::await_retry_or_panic!(
+        collections.update_one(query.clone(), update.clone(), options.clone()),
+        10,
+        "Collection mint was NOT added to database".to_string(),
+        &key,
+    );
+
+    if let Some(rarity) = &key.rarity {
+        update_rarity_count(pool, key, rarity, 1).await?;
+    }
+
+    update_holder_count(pool, key, owner, 1).await
+}
+
+pub(crate) async fn record_collection_transfer(
+    pool: &mongodb::Client,
+    key: &CollectionKey,
+    old_owner: String,
+    new_owner: String,
+) -> anyhow::Result<()> {
+
+    info!(
+        target: crate::INDEXER,
+        "Moving token of collection {} from {} to {}",
+        key.id(), &old_owner, &new_owner,
+    );
+
+    update_holder_count(pool, key, old_owner, -1).await?;
+    update_holder_count(pool, key, new_owner, 1).await
+}
+
+pub(crate) async fn record_collection_burn(
+    pool: &mongodb::Client,
+    key: &CollectionKey,
+    owner: String,
+) -> anyhow::Result<()> {
+
+    info!(
+        target: crate::INDEXER,
+        "Adding burn to collection {}",
+        key.id(),
+    );
+
+    let db = pool.database(crate::DB_NAME);
+    let collections = db.collection::<CollectionDB>(super::COLLECTIONS);
+
+    let query = doc!{ "_id": key.id() };
+    let update = doc!{ "$inc": { "supply": -1i64, "burned": 1i64 } };
+
+    crate::await_retry_or_panic!(
+        collections.update_one(query.clone(), update.clone(), None),
+        10,
+        "Collection burn was NOT added to database".to_string(),
+        &key,
+    );
+
+    if let Some(rarity) = &key.rarity {
+        update_rarity_count(pool, key, rarity, -1).await?;
+    }
+
+    update_holder_count(pool, key, owner, -1).await
+}
+
+/// Rarities are values, not field names, so the distribution is a list updated in place
+async fn update_rarity_count(
+    pool: &mongodb::Client,
+    key: &CollectionKey,
+    rarity: &str,
+    change: i64,
+) -> anyhow::Result<()> {
+
+    let db = pool.database(crate::DB_NAME);
+    let collections = db.collection::<CollectionDB>(super::COLLECTIONS);
+
+    let query = doc!{ "_id": key.id(), "rarity_distribution.rarity": rarity };
+    let update = doc!{ "$inc": { "rarity_distribution.$.count": change } };
+
+    let result = crate::await_retry_or_panic!(
+        collections.update_one(query.clone(), update.clone(), None),
+        10,
+        "Collection rarity was NOT updated in database".to_string(),
+        (&key, &rarity),
+    );
+
+    if result.map(|result| result.matched_count).unwrap_or(0) == 0 {
+        let query = doc!{ "_id": key.id() };
+        let update = doc!{ "$push": { "rarity_distribution": { "rarity": rarity, "count": change } } };
+
+        crate::await_retry_or_panic!(
+            collections.update_one(query.clone(), update.clone(), None),
+            10,
+            "Collection rarity was NOT added to database".to_string(),
+            (&key, &rarity),
+        );
+    }
+
+    Ok(())
+}
+
+/// New token count of a holder and the change of the collection's holder count. Counts are clamped at zero:
+/// tokens received before indexing were never counted, a wallet sending them must not go below zero
+fn get_holder_count_change(
+    previous_count: i64,
+    change: i64,
+) -> (i64, i64) {
+    let previous_count = previous_count.max(0);
+    let count = (previous_count + change).max(0);
+
+    let holders_change = match (previous_count > 0, count > 0) {
+        (false, true) => 1,
+        (true, false) => -1,
+        _ => 0,
+    };
+
+    (count, holders_change)
+}
+
+/// Changes the number of tokens the wallet holds in the collection and
+/// counts the wallet as a holder while that number is positive
+async fn update_holder_count(
+    pool: &mongodb::Client,
+    key: &CollectionKey,
+    owner: String,
+    change: i64,
+) -> anyhow::Result<()> {
+
+    let db = pool.database(crate::DB_NAME);
+    let holders = db.collection::<CollectionHolderDB>(super::COLLECTION_HOLDERS);
+    let collections = db.collection::<Document>(super::COLLECTIONS);
+
+    let collection_id = key.id();
+
+    // A pipeline update, `$inc` can't be clamped
+    let query = doc!{ "_id": format!("{}:{}", collection_id, owner) };
+    let update = vec![doc!{
+        "$set": {
+            "count": { "$max": [{ "$add": [{ "$max": [{ "$ifNull": ["$count", 0i64] }, 0i64] }, change] }, 0i64] },
+            "collection_id": collection_id.clone(),
+            "owner": owner.clone(),
+        },
+    }];
+    let options = FindOneAndUpdateOptions::builder()
+        .upsert(true)
+        .return_document(ReturnDocument::Before)
+        .build();
+
+    let previous_holder = crate::await_retry_or_panic!(
+        holders.find_one_and_update(query.clone(), update.clone(), options.clone()),
+        10,
+        "Collection holder was NOT updated in database".to_string(),
+        (&collection_id, &owner),
+    );
+
+    let previous_count = previous_holder.flatten().map(|holder| holder.count).unwrap_or(0);
+    let (count, holders_change) = get_holder_count_change(previous_count, change);
+
+    if count == 0 {
+        crate::await_retry_or_panic!(
+            holders.delete_one(query.clone(), None),
+            10,
+            "Collection holder was NOT removed from database".to_string(),
+            (&collection_id, &owner),
+        );
+    }
+
+    if holders_change == 0 {
+        return Ok(());
+    }
+
+    let query = doc!{ "_id": collection_id.clone() };
+    let update = doc!{ "$inc": { "holders": holders_change } };
+
+    crate::await_retry_or_panic!(
+        collections.update_one(query.clone(), update.clone(), None),
+        10,
+        "Collection holders were NOT updated in database".to_string(),
+        &collection_id,
+    );
+
+    Ok(())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn holders_are_counted_when_crossing_zero() {
+        assert_eq!(get_holder_count_change(0, 1), (1, 1));
+        assert_eq!(get_holder_count_change(1, 1), (2, 0));
+        assert_eq!(get_holder_count_change(2, -1), (1, 0));
+        assert_eq!(get_holder_count_change(1, -1), (0, -1));
+    }
+
+    #[test]
+    fn tokens_received_before_indexing_dont_count() {
+        // Sending an uncounted token leaves the wallet at zero
+        assert_eq!(get_holder_count_change(0, -1), (0, 0));
+        assert_eq!(get_holder_count_change(1, -2), (0, -1));
+
+        // So an indexed token received afterwards makes it a holder once
+        assert_eq!(get_holder_count_change(0, 1), (1, 1));
+
+        // Entries left below zero by earlier versions are read as zero
+        assert_eq!(get_holder_count_change(-1, 1), (1, 1));
+        assert_eq!(get_holder_count_change(-1, -1), (0, 0));
+    }
+}
@@ -0,0 +1,136 @@
+use mongodb::bson::doc;
+use mongodb::options::UpdateOptions;
+use near_indexer::near_primitives::types::AccountId;
+use serde::{ Deserialize, Serialize };
+
+use tracing::info;
+
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub(crate) struct ContractDB {
+    _id: String,
+    pub contract_id: AccountId,
+    pub owner_id: Option<String>,
+    pub metadata: Option<ContractMetadata>,
+    /// Initialized with `new_default_meta`, the metadata is hardcoded in the contract
+    #[serde(default)]
+    pub default_metadata: bool,
+    /// A metadata update was logged but the new metadata wasn't found in the call args
+    #[serde(default)]
+    pub metadata_stale: bool,
+    pub metadata_receipt_id: Option<String>,
+}
+
+/// NEP-177 contract metadata
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub(crate) struct ContractMetadata {
+    pub spec: Option<String>,
+    pub name: Option<String>,
+    pub symbol: Option<String>,
+    pub icon: Option<String>,
+    pub base_uri: Option<String>,
+    pub reference: Option<String>,
+    pub reference_hash: Option<String>,
+}
+
+/// Stores the metadata passed to the contract `new` method
+pub(crate) async fn init_contract(
+    pool: &mongodb::Client,
+    contract_id: AccountId,
+    owner_id: Option<String>,
+    metadata: Option<ContractMetadata>,
+    receipt_id: String,
+) -> anyhow::Result<()> {
+
+    let update = doc!{ "$set": {
+        "contract_id": contract_id.to_string(),
+        "owner_id": owner_id,
+        "default_metadata": metadata.is_none(),
+        "metadata_stale": false,
+        "metadata": mongodb::bson::to_bson(&metadata)?,
+        "metadata_receipt_id": receipt_id,
+    }};
+
+    update_contract(pool, contract_id, update).await
+}
+
+pub(crate) async fn update_contract_metadata(
+    pool: &mongodb::Client,
+    contract_id: AccountId,
+    metadata: ContractMetadata,
+    receipt_id: String,
+) -> anyhow::Result<()> {
+
+    let update = doc!{ "$set": {
+        "contract_id": contract_id.to_string(),
+        "default_metadata": false,
+        "metadata_stale": false,
+        "metadata": mongodb::bson::to_bson(&metadata)?,
+        "metadata_receipt_id": receipt_id,
+    }};
+
+    update_contract(pool, contract_id, update).await
+}
+
+pub(crate) async fn mark_contract_metadata_stale(
+    pool: &mongodb::Client,
+    contract_id: AccountId,
+    receipt_id: String,
+) -> anyhow::Result<()> {
+
+    let update = doc!{ "$set": {
+        "contract_id": contract_id.to_string(),
+        "metadata_stale": true,
+        "metadata_receipt_id": receipt_id,
+    }};
+
+    update_contract(pool, contract_id, update).await
+}
+
+async fn update_contract(
+    pool: &mongodb::Client,
+    contract_id: AccountId,
+    update: mongodb::bson::Document,
+) -> anyhow::Result<()> {
+
+    info!(
+        target: crate::INDEXER,
+        "Updating contract {}: {}",
+        &contract_id, &update,
+    );
+
+    let db = pool.database(crate::DB_NAME);
+    let contracts_collection = db.collection::<ContractDB>(super::CONTRACTS);
+
+    let query = doc!{ "_id": contract_id.to_string() };
+    let options = UpdateOptions::builder().upsert(true).build();
+
+    crate::await_retry_or_panic!(
+        contracts_collection.update_one(query.clone(), update.clone(), options.clone()),
+        10,
+        "Contract was NOT updated in database".to_string(),
+        &contract_id,
+    );
+
+    Ok(())
+}
+
+pub(crate) async fn get_contract(
+    pool: &mongodb::Client,
+    contract_id: AccountId,
+) -> anyhow::Result<Option<ContractDB>> {
+
+    let db = pool.database(crate::DB_NAME);
+    let contracts_collection = db.collection::<ContractDB>(super::CONTRACTS);
+
+    let query = doc!{ "_id": contract_id.to_string() };
+
+    let contract = crate::await_retry_or_panic!(
+        contracts_collection.find_one(query.clone(), None),
+        10,
+        "Contract was NOT read from database".to_string(),
+        &contract_id,
+    );
+
+    Ok(contract.flatten())
+}
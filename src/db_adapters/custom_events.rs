@@ -0,0 +1,79 @@
+use mongodb::bson::doc;
+use mongodb::options::ReplaceOptions;
+use near_indexer::near_primitives::types::AccountId;
+use serde::{ Deserialize, Serialize };
+
+use tracing::info;
+use crate::events::CustomEvent;
+use crate::models::receipt_context::ReceiptContext;
+
+
+/// Record of a single data entry of a custom event. The id is derived from its position in the receipt,
+/// so replayed blocks overwrite their records
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub(crate) struct CustomEventDB {
+    _id: String,
+    pub contract_id: AccountId,
+    pub receipt_id: String,
+    pub block_height: u64,
+    /// Block timestamp in nanoseconds
+    pub block_timestamp: u64,
+    pub standard: String,
+    pub version: String,
+    pub event: String,
+    pub fields: serde_json::Map<String, serde_json::Value>,
+}
+
+impl CustomEventDB {
+    pub(crate) fn new(
+        context: &ReceiptContext,
+        event: &CustomEvent,
+        entry_index: usize,
+        fields: serde_json::Map<String, serde_json::Value>,
+    ) -> Self {
+        Self {
+            _id: get_custom_event_db_id(&context.receipt_id, event.log_index, entry_index),
+            contract_id: context.contract_id.clone(),
+            receipt_id: context.receipt_id.clone(),
+            block_height: context.block_height,
+            block_timestamp: context.block_timestamp,
+            standard: event.standard.clone(),
+            version: event.version.clone(),
+            event: event.event.clone(),
+            fields,
+        }
+    }
+}
+
+fn get_custom_event_db_id(receipt_id: &str, log_index: usize, entry_index: usize) -> String {
+    format!("{}:{}:{}", receipt_id, log_index, entry_index)
+}
+
+/// Stores a custom event record in the collection set by its schema
+pub(crate) async fn store_custom_event(
+    pool: &mongodb::Client,
+    collection: &str,
+    record: CustomEventDB,
+) -> anyhow::Result<()> {
+
+    info!(
+        target: crate::INDEXER,
+        "Adding {} event to {}: {:#?}",
+        &record.event, &collection, &record,
+    );
+
+    let db = pool.database(crate::DB_NAME);
+    let custom_event_collection = db.collection::<CustomEventDB>(collection);
+
+    let query = doc!{ "_id": record._id.clone() };
+    let options = ReplaceOptions::builder().upsert(true).build();
+
+    crate::await_retry_or_panic!(
+        custom_event_collection.replace_one(query.clone(), record.clone(), options.clone()),
+        10,
+        "Custom event was NOT added to database".to_string(),
+        &record,
+    );
+
+    Ok(())
+}
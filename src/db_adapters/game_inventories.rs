@@ -0,0 +1,115 @@
+use mongodb::bson::doc;
+use mongodb::options::UpdateOptions;
+use near_indexer::near_primitives::types::AccountId;
+use serde::{ Deserialize, Serialize };
+
+use tracing::info;
+use crate::utils;
+
+
+/// Items of a game held by a wallet, a projection of `near_nfts` for the game backend
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub(crate) struct GameInventoryDB {
+    _id: String,
+    pub game_id: String,
+    pub owner: String,
+    #[serde(default)]
+    pub items: Vec<InventoryItem>,
+}
+
+/// Summary of a token, refreshed on metadata updates
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub(crate) struct InventoryItem {
+    pub contract_id: AccountId,
+    pub token_id: String,
+    pub title: Option<String>,
+    pub rarity: Option<String>,
+    pub nft_type: Option<String>,
+}
+
+/// Game ids are free-form, so the id hashes an unambiguous JSON array
+fn get_inventory_id(game_id: &str, owner: &str) -> String {
+    utils::keccak256_hash_string(serde_json::json!([game_id, owner]).to_string())
+}
+
+
+/// Adds the item to the inventory, replacing the summary of the same token
+pub(crate) async fn add_inventory_item(
+    pool: &mongodb::Client,
+    game_id: String,
+    owner: String,
+    item: InventoryItem,
+) -> anyhow::Result<()> {
+
+    info!(
+        target: crate::INDEXER,
+        "Adding item {} of contract {} to inventory of {} in game {}",
+        &item.token_id, &item.contract_id, &owner, &game_id,
+    );
+
+    remove_inventory_item(pool, game_id.clone(), owner.clone(), item.contract_id.clone(), item.token_id.clone()).await?;
+
+    let db = pool.database(crate::DB_NAME);
+    let inventories = db.collection::<GameInventoryDB>(super::GAME_INVENTORIES);
+
+    let query = doc!{ "_id": get_inventory_id(&game_id, &owner) };
+    let update = doc!{
+        "$push": { "items": mongodb::bson::to_bson(&item)? },
+        "$setOnInsert": { "game_id": game_id.clone(), "owner": owner.clone() },
+    };
+    let options = UpdateOptions::builder().upsert(true).build();
+
+    crate::await_retry_or_panic!(
+        inventories.update_one(query.clone(), update.clone(), options.clone()),
+        10,
+        "Inventory item was NOT added to database".to_string(),
+        (&game_id, &owner, &item),
+    );
+
+    Ok(())
+}
+
+pub(crate) async fn remove_inventory_item(
+    pool: &mongodb::Client,
+    game_id: String,
+    owner: String,
+    contract_id: AccountId,
+    token_id: String,
+) -> anyhow::Result<()> {
+
+    let db = pool.database(crate::DB_NAME);
+    let inventories = db.collection::<GameInventoryDB>(super::GAME_INVENTORIES);
+
+    let query = doc!{ "_id": get_inventory_id(&game_id, &owner) };
+    let update = doc!{ "$pull": { "items": { "contract_id": contract_id.to_string(), "token_id": token_id.clone() } } };
+
+    crate::await_retry_or_panic!(
+        inventories.update_one(query.clone(), update.clone(), None),
+        10,
+        "Inventory item was NOT removed from database".to_string(),
+        (&game_id, &owner, &contract_id, &token_id),
+    );
+
+    Ok(())
+}
+
+pub(crate) async fn get_inventory(
+    pool: &mongodb::Client,
+    game_id: String,
+    owner: String,
+) -> anyhow::Result<Option<GameInventoryDB>> {
+
+    let db = pool.database(crate::DB_NAME);
+    let inventories = db.collection::<GameInventoryDB>(super::GAME_INVENTORIES);
+
+    let query = doc!{ "game_id": game_id.clone(), "owner": owner.clone() };
+
+    let inventory = crate::await_retry_or_panic!(
+        inventories.find_one(query.clone(), None),
+        10,
+        "Inventory was NOT read from database".to_string(),
+        (&game_id, &owner),
+    );
+
+    Ok(inventory.flatten())
+}
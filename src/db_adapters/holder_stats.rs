@@ -0,0 +1,296 @@
+use futures::TryStreamExt;
+use mongodb::bson::{ doc, Bson };
+use mongodb::options::{ FindOptions, UpdateOptions };
+use serde::{ Deserialize, Serialize };
+
+use tracing::info;
+use crate::utils;
+use super::tokens::TokenDB;
+
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum HolderScope {
+    Contract,
+    Game,
+}
+
+impl HolderScope {
+    pub(crate) fn as_str(&self) -> &'static str {
+        match self {
+            HolderScope::Contract => "contract",
+            HolderScope::Game => "game",
+        }
+    }
+}
+
+/// Tokens of a contract or game held by a wallet, a leaderboard entry
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub(crate) struct HolderStatsDB {
+    _id: String,
+    pub scope: HolderScope,
+    pub scope_id: String,
+    pub owner: String,
+    pub token_count: i64,
+    /// Sum of the trait rarity scores of the held tokens, see `HolderCredit`
+    pub rarity_score: f64,
+}
+
+/// What the current holder of a token is credited with, debited exactly when the token leaves.
+/// The score follows the token's trait rarity score
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub(crate) struct HolderCredit {
+    pub owner: String,
+    pub game_id: Option<String>,
+    pub rarity_score: f64,
+}
+
+/// Credited tokens of a holder within a contract and game, rescored together
+#[derive(Debug, Deserialize, Clone)]
+pub(crate) struct CreditedHoldings {
+    pub contract_id: String,
+    pub owner: String,
+    pub game_id: Option<String>,
+    pub token_count: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub(crate) struct WalletStatsDB {
+    /// Wallet id
+    _id: String,
+    pub first_acquired_at_height: Option<u64>,
+    /// Block timestamp in nanoseconds
+    pub first_acquired_at_timestamp: Option<u64>,
+    #[serde(default)]
+    pub mints_received: u64,
+    #[serde(default)]
+    pub transfers_received: u64,
+    #[serde(default)]
+    pub transfers_sent: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum LeaderboardOrder {
+    TokenCount,
+    RarityScore,
+}
+
+fn get_holder_stats_id(scope: HolderScope, scope_id: &str, owner: &str) -> String {
+    utils::keccak256_hash_string(serde_json::json!([scope.as_str(), scope_id, owner]).to_string())
+}
+
+
+/// Changes the holdings of the wallet, entries left without tokens are removed
+pub(crate) async fn update_holder_stats(
+    pool: &mongodb::Client,
+    scope: HolderScope,
+    scope_id: String,
+    owner: String,
+    token_count_change: i64,
+    rarity_score_change: f64,
+) -> anyhow::Result<()> {
+
+    info!(
+        target: crate::INDEXER,
+        "Updating {} {} holdings of {} by {} tokens and {} rarity",
+        scope.as_str(), &scope_id, &owner, &token_count_change, &rarity_score_change,
+    );
+
+    let db = pool.database(crate::DB_NAME);
+    let holder_stats = db.collection::<HolderStatsDB>(super::HOLDER_STATS);
+
+    let holder_stats_id = get_holder_stats_id(scope, &scope_id, &owner);
+
+    let query = doc!{ "_id": holder_stats_id.clone() };
+    let update = doc!{
+        "$inc": { "token_count": token_count_change, "rarity_score": rarity_score_change },
+        "$setOnInsert": { "scope": scope.as_str(), "scope_id": scope_id.clone(), "owner": owner.clone() },
+    };
+    let options = UpdateOptions::builder().upsert(true).build();
+
+    crate::await_retry_or_panic!(
+        holder_stats.update_one(query.clone(), update.clone(), options.clone()),
+        10,
+        "Holder stats were NOT updated in database".to_string(),
+        (&scope_id, &owner),
+    );
+
+    if token_count_change < 0 {
+        let query = doc!{ "_id": holder_stats_id, "token_count": { "$lte": 0 } };
+
+        crate::await_retry_or_panic!(
+            holder_stats.delete_one(query.clone(), None),
+            10,
+            "Empty holder stats were NOT removed from database".to_string(),
+            (&scope_id, &owner),
+        );
+    }
+
+    Ok(())
+}
+
+pub(crate) async fn set_holder_credit(
+    pool: &mongodb::Client,
+    token_db_id: String,
+    holder_credit: Option<HolderCredit>,
+) -> anyhow::Result<()> {
+
+    let db = pool.database(crate::DB_NAME);
+    let token_collection = db.collection::<TokenDB>(super::TOKEN_TABLE);
+
+    let query = doc!{ "_id": token_db_id.clone() };
+    let update = match &holder_credit {
+        Some(holder_credit) => doc!{ "$set": { "holder_credit": mongodb::bson::to_bson(holder_credit)? } },
+        None => doc!{ "$set": { "holder_credit": Bson::Null } },
+    };
+
+    crate::await_retry_or_panic!(
+        token_collection.update_one(query.clone(), update.clone(), None),
+        10,
+        "Holder credit was NOT updated in database".to_string(),
+        (&token_db_id, &holder_credit),
+    );
+
+    Ok(())
+}
+
+/// Unburned tokens whose credit is missing or disagrees with their holder or score,
+/// ex. tokens scored or transferred before holder credits followed score changes
+pub(crate) async fn get_miscredited_tokens(
+    pool: &mongodb::Client,
+) -> anyhow::Result<Vec<TokenDB>> {
+
+    let db = pool.database(crate::DB_NAME);
+    let token_collection = db.collection::<TokenDB>(super::TOKEN_TABLE);
+
+    let query = doc!{
+        "metadata": { "$exists": true },
+        "burned": { "$ne": true },
+        "current_owner": { "$type": "string" },
+        "$or": [
+            { "holder_credit": Bson::Null },
+            { "$expr": { "$ne": ["$holder_credit.owner", "$current_owner"] } },
+            { "$expr": { "$ne": ["$holder_credit.rarity_score", { "$ifNull": ["$trait_rarity.score", 0f64] }] } },
+        ],
+    };
+
+    let cursor = crate::await_retry_or_panic!(
+        token_collection.find(query.clone(), None),
+        10,
+        "Miscredited tokens were NOT read from database".to_string(),
+        &query,
+    );
+
+    match cursor {
+        Some(cursor) => Ok(cursor.try_collect().await?),
+        None => Ok(Vec::new()),
+    }
+}
+
+/// Counts a token received by the wallet, by mint or transfer
+pub(crate) async fn record_wallet_acquisition(
+    pool: &mongodb::Client,
+    owner: String,
+    minted: bool,
+    block_height: u64,
+    block_timestamp: u64,
+) -> anyhow::Result<()> {
+
+    let db = pool.database(crate::DB_NAME);
+    let wallet_stats = db.collection::<WalletStatsDB>(super::WALLET_STATS);
+
+    let counter = match minted {
+        true => "mints_received",
+        false => "transfers_received",
+    };
+
+    let query = doc!{ "_id": owner.clone() };
+    let update = doc!{
+        "$inc": { counter: 1i64 },
+        "$min": { "first_acquired_at_height": block_height as i64, "first_acquired_at_timestamp": block_timestamp as i64 },
+    };
+    let options = UpdateOptions::builder().upsert(true).build();
+
+    crate::await_retry_or_panic!(
+        wallet_stats.update_one(query.clone(), update.clone(), options.clone()),
+        10,
+        "Wallet acquisition was NOT added to database".to_string(),
+        &owner,
+    );
+
+    Ok(())
+}
+
+pub(crate) async fn record_wallet_transfer_sent(
+    pool: &mongodb::Client,
+    owner: String,
+) -> anyhow::Result<()> {
+
+    let db = pool.database(crate::DB_NAME);
+    let wallet_stats = db.collection::<WalletStatsDB>(super::WALLET_STATS);
+
+    let query = doc!{ "_id": owner.clone() };
+    let update = doc!{ "$inc": { "transfers_sent": 1i64 } };
+    let options = UpdateOptions::builder().upsert(true).build();
+
+    crate::await_retry_or_panic!(
+        wallet_stats.update_one(query.clone(), update.clone(), options.clone()),
+        10,
+        "Wallet transfer was NOT added to database".to_string(),
+        &owner,
+    );
+
+    Ok(())
+}
+
+/// Top holders of a contract or game, read from an index sorted like the leaderboard
+pub(crate) async fn get_leaderboard(
+    pool: &mongodb::Client,
+    scope: HolderScope,
+    scope_id: String,
+    order: LeaderboardOrder,
+    limit: i64,
+) -> anyhow::Result<Vec<HolderStatsDB>> {
+
+    let db = pool.database(crate::DB_NAME);
+    let holder_stats = db.collection::<HolderStatsDB>(super::HOLDER_STATS);
+
+    let query = doc!{ "scope": scope.as_str(), "scope_id": scope_id.clone() };
+    let sort = match order {
+        LeaderboardOrder::TokenCount => doc!{ "token_count": -1 },
+        LeaderboardOrder::RarityScore => doc!{ "rarity_score": -1 },
+    };
+    let options = FindOptions::builder().sort(sort).limit(limit).build();
+
+    let cursor = crate::await_retry_or_panic!(
+        holder_stats.find(query.clone(), options.clone()),
+        10,
+        "Leaderboard was NOT read from database".to_string(),
+        &query,
+    );
+
+    match cursor {
+        Some(cursor) => Ok(cursor.try_collect().await?),
+        None => Ok(Vec::new()),
+    }
+}
+
+pub(crate) async fn get_wallet_stats(
+    pool: &mongodb::Client,
+    owner: String,
+) -> anyhow::Result<Option<WalletStatsDB>> {
+
+    let db = pool.database(crate::DB_NAME);
+    let wallet_stats = db.collection::<WalletStatsDB>(super::WALLET_STATS);
+
+    let query = doc!{ "_id": owner.clone() };
+
+    let stats = crate::await_retry_or_panic!(
+        wallet_stats.find_one(query.clone(), None),
+        10,
+        "Wallet stats were NOT read from database".to_string(),
+        &owner,
+    );
+
+    Ok(stats.flatten())
+}
@@ -0,0 +1,83 @@
+use mongodb::bson::{ doc, Document };
+use mongodb::options::IndexOptions;
+use mongodb::IndexModel;
+
+use tracing::info;
+
+
+fn index(keys: Document, unique: bool) -> IndexModel {
+    IndexModel::builder()
+        .keys(keys)
+        .options(IndexOptions::builder().unique(unique).build())
+        .build()
+}
+
+/// Creates the indexes queried by the indexer and the backend. Existing indexes are left as they are
+pub(crate) async fn create_indexes(
+    pool: &mongodb::Client,
+) -> anyhow::Result<()> {
+
+    let db = pool.database(crate::DB_NAME);
+
+    let indexes: Vec<(&str, Vec<IndexModel>)> = vec![
+        (super::TOKEN_TABLE, vec![
+            index(doc!{ "current_owner": 1 }, false),
+            index(doc!{ "contract_id": 1, "token_id": 1 }, false),
+            index(doc!{ "legacy_id": 1 }, false),
+            index(doc!{ "metadata.expires_at": 1 }, false),
+            index(doc!{ "metadata.starts_at": 1 }, false),
+            // A collection has a single text index, see `tokens::search_tokens`
+            index(doc!{ "metadata.title": "text", "metadata.description": "text", "metadata.collection_name": "text" }, false),
+            index(doc!{ "trait_rarity.traits": 1 }, false),
+            index(doc!{ "trait_rarity.collection_id": 1, "trait_rarity.score": -1 }, false),
+        ]),
+        (super::WALLET_TOKENS, vec![
+            index(doc!{ "tokens": 1 }, false),
+        ]),
+        (super::TOKEN_APPROVALS, vec![
+            index(doc!{ "owner_id": 1 }, false),
+        ]),
+        (super::STORAGE_REGISTRATIONS, vec![
+            index(doc!{ "contract_id": 1, "account_id": 1 }, true),
+        ]),
+        (super::SOULBOUND_TOKENS, vec![
+            index(doc!{ "registry_id": 1, "owner": 1 }, false),
+        ]),
+        (super::COLLECTIONS, vec![
+            index(doc!{ "contract_id": 1 }, false),
+        ]),
+        (super::COLLECTION_TRAITS, vec![
+            index(doc!{ "collection_id": 1 }, false),
+        ]),
+        (super::GAME_INVENTORIES, vec![
+            index(doc!{ "game_id": 1, "owner": 1 }, true),
+        ]),
+        (super::HOLDER_STATS, vec![
+            index(doc!{ "scope": 1, "scope_id": 1, "token_count": -1 }, false),
+            index(doc!{ "scope": 1, "scope_id": 1, "rarity_score": -1 }, false),
+        ]),
+        (super::COLLECTION_HOLDERS, vec![
+            index(doc!{ "collection_id": 1, "count": -1 }, false),
+        ]),
+    ];
+
+    for (collection, models) in indexes {
+
+        info!(
+            target: crate::INDEXER,
+            "Creating indexes on {}",
+            &collection,
+        );
+
+        let collection = db.collection::<Document>(collection);
+
+        crate::await_retry_or_panic!(
+            collection.create_indexes(models.clone(), None),
+            10,
+            "Indexes were NOT created".to_string(),
+            &models,
+        );
+    }
+
+    Ok(())
+}
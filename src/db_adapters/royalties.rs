@@ -0,0 +1,54 @@
+use near_indexer::near_primitives::types::AccountId;
+use serde::{ Deserialize, Serialize };
+
+use tracing::info;
+
+
+/// NEP-199 payout of a single `nft_transfer_payout` call.
+/// Linked to the transfer event logged by the same receipt through `receipt_id` and `token_id`
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub(crate) struct RoyaltyDistributionDB {
+    pub contract_id: AccountId,
+    pub token_id: String,
+    pub receipt_id: String,
+    pub game_id: Option<String>,
+    pub receiver_id: String,
+    pub approval_id: Option<u64>,
+    pub memo: Option<String>,
+    /// Sale amount in yoctoNEAR
+    pub balance: String,
+    pub max_len_payout: Option<u32>,
+    /// Stored as a list, account ids contain dots and can't be used as field names
+    pub payout: Vec<PayoutDB>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub(crate) struct PayoutDB {
+    pub account_id: String,
+    /// Amount in yoctoNEAR
+    pub amount: String,
+}
+
+pub(crate) async fn store_royalty_distribution(
+    pool: &mongodb::Client,
+    distribution: RoyaltyDistributionDB,
+) -> anyhow::Result<()> {
+
+    info!(
+        target: crate::INDEXER,
+        "Adding royalty distribution to DB: {:#?}",
+        &distribution,
+    );
+
+    let db = pool.database(crate::DB_NAME);
+    let royalties_collection = db.collection::<RoyaltyDistributionDB>(super::ROYALTY_DISTRIBUTIONS);
+
+    crate::await_retry_or_panic!(
+        royalties_collection.insert_one(distribution.clone(), None),
+        10,
+        "Royalty distribution was NOT added to database".to_string(),
+        &distribution,
+    );
+
+    Ok(())
+}
@@ -0,0 +1,171 @@
+use mongodb::bson::{ doc, Document };
+use mongodb::options::{ ReplaceOptions, UpdateOptions };
+use near_indexer::near_primitives::types::AccountId;
+use serde::{ Deserialize, Serialize };
+
+use tracing::info;
+
+
+/// NEP-393 soulbound token. Token ids are unique per registry and issuer
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub(crate) struct SoulboundTokenDB {
+    _id: String,
+    pub registry_id: AccountId,
+    pub issuer: String,
+    pub token_id: u64,
+    pub owner: String,
+    pub class: Option<u64>,
+    pub issued_at: Option<u64>,
+    pub expires_at: Option<u64>,
+    pub reference: Option<String>,
+    pub revoked: bool,
+    pub burned: bool,
+}
+
+impl SoulboundTokenDB {
+    pub(crate) fn new(
+        registry_id: AccountId,
+        issuer: String,
+        token_id: u64,
+        owner: String,
+    ) -> Self {
+        Self {
+            _id: get_soulbound_token_db_id(&registry_id, &issuer, token_id),
+            registry_id,
+            issuer,
+            token_id,
+            owner,
+            class: None,
+            issued_at: None,
+            expires_at: None,
+            reference: None,
+            revoked: false,
+            burned: false,
+        }
+    }
+}
+
+/// Account ids can't contain ':', so the id is unambiguous
+fn get_soulbound_token_db_id(registry_id: &AccountId, issuer: &str, token_id: u64) -> String {
+    format!("{}:{}:{}", registry_id, issuer, token_id)
+}
+
+pub(crate) async fn store_soulbound_token(
+    pool: &mongodb::Client,
+    token: SoulboundTokenDB,
+) -> anyhow::Result<()> {
+
+    info!(
+        target: crate::INDEXER,
+        "Adding soulbound token to DB: {:#?}",
+        &token,
+    );
+
+    let db = pool.database(crate::DB_NAME);
+    let sbt_collection = db.collection::<SoulboundTokenDB>(super::SOULBOUND_TOKENS);
+
+    let query = doc!{ "_id": token._id.clone() };
+    let options = ReplaceOptions::builder().upsert(true).build();
+
+    crate::await_retry_or_panic!(
+        sbt_collection.replace_one(query.clone(), token.clone(), options.clone()),
+        10,
+        "Soulbound token was NOT added to database".to_string(),
+        &token,
+    );
+
+    Ok(())
+}
+
+/// Applies the update to the given tokens of the issuer
+pub(crate) async fn update_soulbound_tokens(
+    pool: &mongodb::Client,
+    registry_id: AccountId,
+    issuer: String,
+    token_ids: &[u64],
+    update: Document,
+) -> anyhow::Result<()> {
+
+    info!(
+        target: crate::INDEXER,
+        "Updating soulbound tokens {:?} of issuer {}: {}",
+        &token_ids, &issuer, &update,
+    );
+
+    let db = pool.database(crate::DB_NAME);
+    let sbt_collection = db.collection::<SoulboundTokenDB>(super::SOULBOUND_TOKENS);
+
+    let ids: Vec<String> = token_ids.iter().map(|token_id| get_soulbound_token_db_id(&registry_id, &issuer, *token_id)).collect();
+
+    let query = doc!{ "_id": { "$in": ids } };
+
+    crate::await_retry_or_panic!(
+        sbt_collection.update_many(query.clone(), update.clone(), None),
+        10,
+        "Soulbound tokens were NOT updated in database".to_string(),
+        (&issuer, &token_ids),
+    );
+
+    Ok(())
+}
+
+/// Moves every token of the registry held by `from` to `to`
+pub(crate) async fn transfer_soul(
+    pool: &mongodb::Client,
+    registry_id: AccountId,
+    from: String,
+    to: String,
+) -> anyhow::Result<()> {
+
+    info!(
+        target: crate::INDEXER,
+        "Soul transfer from {} to {} in registry {}",
+        &from, &to, &registry_id,
+    );
+
+    let db = pool.database(crate::DB_NAME);
+    let sbt_collection = db.collection::<SoulboundTokenDB>(super::SOULBOUND_TOKENS);
+
+    let query = doc!{ "registry_id": registry_id.to_string(), "owner": from.clone() };
+    let update = doc!{ "$set": { "owner": to.clone() } };
+
+    crate::await_retry_or_panic!(
+        sbt_collection.update_many(query.clone(), update.clone(), None),
+        10,
+        "Soul transfer was NOT applied to database".to_string(),
+        (&from, &to),
+    );
+
+    Ok(())
+}
+
+pub(crate) async fn ban_accounts(
+    pool: &mongodb::Client,
+    registry_id: AccountId,
+    accounts: &[String],
+) -> anyhow::Result<()> {
+
+    info!(
+        target: crate::INDEXER,
+        "Banning accounts {:?} in registry {}",
+        &accounts, &registry_id,
+    );
+
+    let db = pool.database(crate::DB_NAME);
+    let banned_collection = db.collection::<Document>(super::SOULBOUND_BANNED_ACCOUNTS);
+
+    for account in accounts {
+        let query = doc!{ "registry_id": registry_id.to_string(), "account_id": account.clone() };
+        let update = doc!{ "$set": { "registry_id": registry_id.to_string(), "account_id": account.clone() } };
+        let options = UpdateOptions::builder().upsert(true).build();
+
+        crate::await_retry_or_panic!(
+            banned_collection.update_one(query.clone(), update.clone(), options.clone()),
+            10,
+            "Banned account was NOT added to database".to_string(),
+            &account,
+        );
+    }
+
+    Ok(())
+}
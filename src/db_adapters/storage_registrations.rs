@@ -0,0 +1,82 @@
+use mongodb::bson::doc;
+use mongodb::options::ReplaceOptions;
+use near_indexer::near_primitives::types::AccountId;
+use serde::{ Deserialize, Serialize };
+
+use tracing::info;
+
+
+/// NEP-145 storage registration of an account on a FT contract. Amounts are yoctoNEAR
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub(crate) struct StorageRegistrationDB {
+    pub contract_id: AccountId,
+    pub account_id: String,
+    pub registered: bool,
+    pub total: String,
+    pub available: String,
+    /// Sum of every deposit attached to `storage_deposit`, refunds included
+    pub deposited: String,
+    /// Sum of every amount sent back to the account by deposits, withdrawals and unregistering
+    pub refunded: String,
+    pub last_receipt_id: String,
+}
+
+pub(crate) async fn store_storage_registration(
+    pool: &mongodb::Client,
+    registration: StorageRegistrationDB,
+) -> anyhow::Result<()> {
+
+    info!(
+        target: crate::INDEXER,
+        "Updating storage registration: {:#?}",
+        &registration,
+    );
+
+    let db = pool.database(crate::DB_NAME);
+    let registrations_collection = db.collection::<StorageRegistrationDB>(super::STORAGE_REGISTRATIONS);
+
+    let query = doc!{ "contract_id": registration.contract_id.to_string(), "account_id": registration.account_id.clone() };
+    let options = ReplaceOptions::builder().upsert(true).build();
+
+    crate::await_retry_or_panic!(
+        registrations_collection.replace_one(query.clone(), registration.clone(), options.clone()),
+        10,
+        "Storage registration was NOT updated in database".to_string(),
+        &registration,
+    );
+
+    Ok(())
+}
+
+pub(crate) async fn get_storage_registration(
+    pool: &mongodb::Client,
+    contract_id: AccountId,
+    account_id: String,
+) -> anyhow::Result<Option<StorageRegistrationDB>> {
+
+    let db = pool.database(crate::DB_NAME);
+    let registrations_collection = db.collection::<StorageRegistrationDB>(super::STORAGE_REGISTRATIONS);
+
+    let query = doc!{ "contract_id": contract_id.to_string(), "account_id": account_id.clone() };
+
+    let registration = crate::await_retry_or_panic!(
+        registrations_collection.find_one(query.clone(), None),
+        10,
+        "Storage registration was NOT read from database".to_string(),
+        (&contract_id, &account_id),
+    );
+
+    Ok(registration.flatten())
+}
+
+/// Whether the account can receive tokens of the contract, ex. before the backend sends rewards
+pub(crate) async fn is_account_registered(
+    pool: &mongodb::Client,
+    contract_id: AccountId,
+    account_id: String,
+) -> anyhow::Result<bool> {
+
+    let registration = get_storage_registration(pool, contract_id, account_id).await?;
+
+    Ok(registration.map(|registration| registration.registered).unwrap_or(false))
+}
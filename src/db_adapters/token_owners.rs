@@ -1,11 +1,13 @@
-use mongodb::bson::doc;
+use std::collections::HashMap;
+use futures::TryStreamExt;
+use mongodb::bson::{ doc, Document };
 use mongodb::options::UpdateOptions;
 use near_indexer::near_primitives::types::AccountId;
 use serde::{ Deserialize, Serialize };
 
-use tracing::info;
+use tracing::{ info, warn };
 use crate::utils;
-use super::tokens::TokenDB;
+use super::tokens::{ OwnershipHistoryEntry, TokenDB };
 
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -20,6 +22,7 @@ pub(crate) async fn add_token_owner(
     contract_id: AccountId,
     token_id: String,
     new_owner: String,
+    history_entry: OwnershipHistoryEntry,
 ) -> anyhow::Result<()> {
 
     info!(
@@ -30,13 +33,13 @@ pub(crate) async fn add_token_owner(
 
     let db = pool.database(crate::DB_NAME);
 
-    let token_id_hash = utils::keccak256_hash_string(format!("{}{}", contract_id, token_id));
+    let token_db_id = utils::get_token_db_id(&contract_id, &token_id);
 
     {
         let wallet_tokens_collection = db.collection::<NearWalletTokensDB>(super::WALLET_TOKENS);
 
         let query = doc!{ "_id": new_owner.clone() };
-        let update = doc!{ "$push": { "tokens": token_id_hash.clone() } };
+        let update = doc!{ "$push": { "tokens": token_db_id.clone() } };
         let options = UpdateOptions::builder().upsert(true).build();
 
         crate::await_retry_or_panic!(
@@ -47,19 +50,23 @@ pub(crate) async fn add_token_owner(
         );
     }
 
-    // Update chain-nfts table to add to token owenership history
+    // Update chain-nfts table to add to token owenership history and set the current owner
     {
         let token_collection = db.collection::<TokenDB>(super::TOKEN_TABLE);
 
-        let query = doc!{ "_id": token_id_hash };
-        let update = doc!{ "$push": { "ownership_history": new_owner.clone() }};
+        // Ids are set as well, a token only known from transfers must be found by `migrate_token_ids`
+        let query = doc!{ "_id": token_db_id };
+        let update = doc!{
+            "$push": { "ownership_history": mongodb::bson::to_bson(&history_entry)? },
+            "$set": { "current_owner": new_owner.clone(), "contract_id": contract_id.to_string(), "token_id": token_id.clone() },
+        };
         let options = UpdateOptions::builder().upsert(true).build();
 
         crate::await_retry_or_panic!(
             token_collection.update_one(query.clone(), update.clone(), options.clone()),
             10,
             "Ownership history was not updated in database".to_string(),
-            (&token_id, &new_owner),
+            (&token_id, &history_entry),
         );
     }
 
@@ -82,10 +89,14 @@ pub(crate) async fn remove_token_owner(
     let db = pool.database(crate::DB_NAME);
     let wallet_tokens_collection = db.collection::<NearWalletTokensDB>(super::WALLET_TOKENS);
 
-    let token_id_hash = utils::keccak256_hash_string(format!("{}{}", contract_id, token_id));
+    // Wallets not migrated yet hold the legacy id
+    let token_db_ids = vec![
+        utils::get_token_db_id(&contract_id, &token_id),
+        utils::get_legacy_token_db_id(&contract_id, &token_id),
+    ];
 
     let query = doc!{ "_id": old_owner.clone() };
-    let update = doc!{ "$pull": { "tokens": token_id_hash } };
+    let update = doc!{ "$pull": { "tokens": { "$in": token_db_ids } } };
     let options = UpdateOptions::builder().upsert(true).build();
 
     crate::await_retry_or_panic!(
@@ -96,4 +107,147 @@ pub(crate) async fn remove_token_owner(
     );
 
     Ok(())
-}
\ No newline at end of file
+}
+
+/// Wallet currently holding the token according to indexed state, the `current_owner` transfers are validated
+/// against. Tokens not moved to versioned ids yet are found by their legacy id
+pub(crate) async fn find_token_owner(
+    pool: &mongodb::Client,
+    contract_id: AccountId,
+    token_id: String,
+) -> anyhow::Result<Option<String>> {
+
+    let db = pool.database(crate::DB_NAME);
+    let token_collection = db.collection::<Document>(super::TOKEN_TABLE);
+    let wallet_tokens_collection = db.collection::<Document>(super::WALLET_TOKENS);
+
+    let token_db_ids = vec![
+        utils::get_token_db_id(&contract_id, &token_id),
+        utils::get_legacy_token_db_id(&contract_id, &token_id),
+    ];
+
+    let query = doc!{ "_id": { "$in": token_db_ids.clone() }, "current_owner": { "$type": "string" } };
+
+    let token = crate::await_retry_or_panic!(
+        token_collection.find_one(query.clone(), None),
+        10,
+        "Token owner was NOT read from database".to_string(),
+        (&contract_id, &token_id),
+    );
+
+    if let Some(owner) = token.flatten().and_then(|token| token.get_str("current_owner").ok().map(|owner| owner.to_string())) {
+        return Ok(Some(owner));
+    }
+
+    // Tokens last moved before `current_owner` was kept
+    let query = doc!{ "tokens": { "$in": token_db_ids } };
+
+    let wallet = crate::await_retry_or_panic!(
+        wallet_tokens_collection.find_one(query.clone(), None),
+        10,
+        "Token owner was NOT read from database".to_string(),
+        (&contract_id, &token_id),
+    );
+
+    Ok(wallet.flatten().and_then(|wallet| wallet.get_str("_id").ok().map(|owner| owner.to_string())))
+}
+
+
+/// Token whose `current_owner` disagrees with the wallets holding it
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub(crate) struct OwnerInconsistency {
+    pub token_db_id: String,
+    pub contract_id: Option<String>,
+    pub token_id: Option<String>,
+    pub current_owner: Option<String>,
+    pub wallet_owners: Vec<String>,
+}
+
+/// Compares `current_owner` of every token with `near_wallet_nfts`.
+/// A token must be held by exactly its current owner, or by nobody once burned
+pub(crate) async fn check_owner_consistency(
+    pool: &mongodb::Client,
+) -> anyhow::Result<Vec<OwnerInconsistency>> {
+
+    let db = pool.database(crate::DB_NAME);
+    let wallet_tokens_collection = db.collection::<Document>(super::WALLET_TOKENS);
+    let token_collection = db.collection::<Document>(super::TOKEN_TABLE);
+
+    let mut wallet_owners: HashMap<String, Vec<String>> = HashMap::new();
+
+    if let Some(mut cursor) = crate::await_retry_or_panic!(
+        wallet_tokens_collection.find(None, None),
+        10,
+        "Wallets were NOT read from database".to_string(),
+        super::WALLET_TOKENS,
+    ) {
+        while let Some(wallet) = cursor.try_next().await? {
+            let owner = wallet.get_str("_id")?.to_string();
+
+            let tokens = wallet.get_array("tokens").cloned().unwrap_or_default();
+
+            for token_db_id in tokens.iter().filter_map(|token| token.as_str()) {
+                wallet_owners.entry(token_db_id.to_string()).or_default().push(owner.clone());
+            }
+        }
+    }
+
+    let mut inconsistencies: Vec<OwnerInconsistency> = Vec::new();
+
+    if let Some(mut cursor) = crate::await_retry_or_panic!(
+        token_collection.find(None, None),
+        10,
+        "Tokens were NOT read from database".to_string(),
+        super::TOKEN_TABLE,
+    ) {
+        while let Some(token) = cursor.try_next().await? {
+            let token_db_id = token.get_str("_id")?.to_string();
+            let current_owner = token.get_str("current_owner").ok().map(|owner| owner.to_string());
+            let owners = wallet_owners.remove(&token_db_id).unwrap_or(Vec::new());
+
+            let consistent = match &current_owner {
+                Some(current_owner) => owners.len() == 1 && &owners[0] == current_owner,
+                None => owners.is_empty(),
+            };
+
+            if !consistent {
+                let inconsistency = OwnerInconsistency {
+                    token_db_id,
+                    contract_id: token.get_str("contract_id").ok().map(|contract_id| contract_id.to_string()),
+                    token_id: token.get_str("token_id").ok().map(|token_id| token_id.to_string()),
+                    current_owner,
+                    wallet_owners: owners,
+                };
+
+                warn!(
+                    target: crate::INDEXER,
+                    "Inconsistent token owner: {:#?}",
+                    &inconsistency,
+                );
+
+                inconsistencies.push(inconsistency);
+            }
+        }
+    }
+
+    // Wallets holding tokens that aren't indexed at all
+    for (token_db_id, owners) in wallet_owners {
+        let inconsistency = OwnerInconsistency {
+            token_db_id,
+            contract_id: None,
+            token_id: None,
+            current_owner: None,
+            wallet_owners: owners,
+        };
+
+        warn!(
+            target: crate::INDEXER,
+            "Wallet holds unknown token: {:#?}",
+            &inconsistency,
+        );
+
+        inconsistencies.push(inconsistency);
+    }
+
+    Ok(inconsistencies)
+}
@@ -1,32 +1,236 @@
-use mongodb::bson::doc;
+use futures::TryStreamExt;
+use std::collections::{ BTreeMap, HashMap };
+use mongodb::bson::{ doc, Bson, Document };
 use near_indexer::near_primitives::types::AccountId;
 use serde::{ Deserialize, Serialize };
 
 use tracing::info;
 use crate::models::token::{ Token, TokenMetadata };
 use crate::utils;
+use super::holder_stats::HolderCredit;
 
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub(crate) struct TokenDB {
+    /// See `utils::get_token_db_id`
     _id: String,
+    /// Hash id used before versioned ids, see `utils::get_legacy_token_db_id`
+    pub legacy_id: Option<String>,
     pub contract_id: AccountId,
     pub token_id: String,
+    /// Latest version of the metadata
     pub metadata: Option<TokenMetadata>,
+    pub minted_by: Option<MintedBy>,
+    #[serde(default)]
+    pub metadata_versions: Vec<MetadataVersion>,
+    /// Kept in sync with `near_wallet_nfts` by `token_owners`, `None` once burned
+    pub current_owner: Option<String>,
+    #[serde(default)]
+    pub burned: bool,
+    pub burned_at_height: Option<u64>,
+    pub burn_receipt_id: Option<String>,
+    pub burn_authorized_id: Option<String>,
+    /// Block heights at which chain time crossed `metadata.expires_at` and `metadata.starts_at`
+    pub expired_at_height: Option<u64>,
+    pub activated_at_height: Option<u64>,
+    /// Rejected `expireGameAsset` and `activateGameAsset` notifications, cleared once the boundary is marked
+    pub expire_retry: Option<NotifyRetry>,
+    pub activate_retry: Option<NotifyRetry>,
+    #[serde(default)]
+    pub ownership_history: Vec<OwnershipHistoryRecord>,
+    /// Off-chain reference and media, set by `models::resolver`
+    pub resolution: Option<TokenResolution>,
+    /// Set once the token's traits are counted, see `models::rarity`
+    pub trait_rarity: Option<TraitRarity>,
+    /// Credited to the current holder's leaderboard entries, see `models::leaderboard`
+    pub holder_credit: Option<HolderCredit>,
+}
+
+/// Rarity of the token's traits within its collection
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub(crate) struct TraitRarity {
+    pub collection_id: String,
+    pub attributes: Vec<TokenTrait>,
+    /// Trait ids of the attributes, see `trait_rarity::get_trait_id`
+    pub traits: Vec<String>,
+    /// Sum of 1 / count over the traits, higher is rarer. Multiplied by the supply it's the usual rarity score
+    pub score: f64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub(crate) struct TokenTrait {
+    pub trait_type: String,
+    pub value: String,
+}
+
+/// Accounts behind the mint receipt
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub(crate) struct MintedBy {
+    pub predecessor_id: AccountId,
+    pub signer_id: AccountId,
+    /// Attached deposit of the whole receipt in yoctoNEAR, shared by the tokens it minted
+    pub deposit: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub(crate) struct MetadataVersion {
+    /// Starts at 1 with the minted metadata
+    pub version: u32,
+    pub metadata: TokenMetadata,
+    /// Missing on the minted metadata of tokens stored before versions were kept, unless their mint was indexed
+    pub block_height: Option<u64>,
+    pub receipt_id: Option<String>,
+    /// "mint", the update method name or "<standard>:<event>"
+    pub source: String,
+}
+
+impl TokenDB {
+    pub(crate) fn id(&self) -> &str {
+        &self._id
+    }
+
+    /// Metadata the token was minted with, `None` when minted without any
+    pub(crate) fn minted_metadata(&self) -> Option<&TokenMetadata> {
+        match self.metadata_versions.first() {
+            Some(metadata_version) if metadata_version.source == "mint" => Some(&metadata_version.metadata),
+            Some(_) => None,
+            // Never updated
+            None => self.metadata.as_ref(),
+        }
+    }
+
+    pub(crate) fn notify_retry(&self, field: TokenTimeField) -> Option<&NotifyRetry> {
+        match field {
+            TokenTimeField::ExpiresAt => self.expire_retry.as_ref(),
+            TokenTimeField::StartsAt => self.activate_retry.as_ref(),
+        }
+    }
+}
+
+/// Backoff of a notification the game backend rejected, see `models::scheduler`
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub(crate) struct NotifyRetry {
+    pub attempts: u32,
+    /// The token isn't due again before this block
+    pub retry_at_height: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub(crate) struct TokenResolution {
+    pub reference: Option<ResolvedContent>,
+    pub media: Option<ResolvedContent>,
+    /// Every resolution attempt, complete or not
+    pub attempts: u32,
+    /// Nothing left to retry, fetch failures are retried
+    pub complete: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub(crate) struct ResolvedContent {
+    pub url: Option<String>,
+    pub status: VerificationStatus,
+    pub content_type: Option<String>,
+    /// Reference JSON, media content isn't stored
+    pub content: Option<serde_json::Value>,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum VerificationStatus {
+    /// sha256 of the content matches the declared hash
+    Verified,
+    HashMismatch,
+    /// Fetched, but the metadata declares no hash
+    NoHash,
+    /// Relative URL without a contract `base_uri`
+    Unresolvable,
+    FetchFailed,
+    /// Larger than the resolver accepts, not retried
+    TooLarge,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum OwnershipEvent {
+    Mint,
+    /// Holder found in a history written before entries were structured, without its mint.
+    /// The token was minted before indexing started
+    Imported,
+    Transfer,
+    Burn,
+    /// Chain time crossed `metadata.expires_at`, the owner is unchanged
+    TokenExpired,
+    /// Chain time crossed `metadata.starts_at`, the owner is unchanged
+    TokenActivated,
+}
+
+/// Ownership change or time event of a token. `owner` is the holder after the change, `None` once burned.
+/// Chain context is only missing on entries migrated from owner strings
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub(crate) struct OwnershipHistoryEntry {
+    pub event: OwnershipEvent,
+    pub owner: Option<String>,
+    pub previous_owner: Option<String>,
+    pub block_height: Option<u64>,
+    /// Block timestamp in nanoseconds
+    pub block_timestamp: Option<u64>,
+    pub receipt_id: Option<String>,
+    pub authorized_id: Option<String>,
+    pub memo: Option<String>,
+}
+
+/// History written before entries were structured holds bare owner strings,
+/// see `migrate_ownership_history`
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(untagged)]
+pub(crate) enum OwnershipHistoryRecord {
+    Entry(OwnershipHistoryEntry),
+    Legacy(String),
 }
 
 pub(crate) async fn store_token(
     pool: &mongodb::Client,
     token: Token,
+    block_height: u64,
+    receipt_id: String,
+    minted_by: MintedBy,
 ) -> anyhow::Result<()> {
 
-    let id_hash = utils::keccak256_hash_string(format!("{}{}", token.contract_id, token.token_id));
+    let token_db_id = utils::get_token_db_id(&token.contract_id, &token.token_id);
+
+    let metadata_versions = match &token.metadata {
+        Some(metadata) => vec![MetadataVersion {
+            version: 1,
+            metadata: metadata.clone(),
+            block_height: Some(block_height),
+            receipt_id: Some(receipt_id),
+            source: "mint".to_string(),
+        }],
+        None => Vec::new(),
+    };
 
     let token_db = TokenDB {
-        _id: id_hash,
+        _id: token_db_id,
+        legacy_id: Some(utils::get_legacy_token_db_id(&token.contract_id, &token.token_id)),
         contract_id: token.contract_id,
         token_id: token.token_id,
         metadata: token.metadata,
+        minted_by: Some(minted_by),
+        metadata_versions,
+        current_owner: None,
+        burned: false,
+        burned_at_height: None,
+        burn_receipt_id: None,
+        burn_authorized_id: None,
+        expired_at_height: None,
+        activated_at_height: None,
+        expire_retry: None,
+        activate_retry: None,
+        ownership_history: Vec::new(),
+        resolution: None,
+        trait_rarity: None,
+        holder_credit: None,
     };
 
     info!(
@@ -46,4 +250,822 @@ pub(crate) async fn store_token(
     );
     
     Ok(())
-}
\ No newline at end of file
+}
+
+/// Marks the token as burned and appends the burn to its history. The token document is kept
+pub(crate) async fn burn_token(
+    pool: &mongodb::Client,
+    contract_id: AccountId,
+    token_id: String,
+    history_entry: OwnershipHistoryEntry,
+) -> anyhow::Result<()> {
+
+    info!(
+        target: crate::INDEXER,
+        "Burning token {} in contract {}: {:#?}",
+        &token_id, &contract_id, &history_entry,
+    );
+
+    let token_db_id = utils::get_token_db_id(&contract_id, &token_id);
+
+    let db = pool.database(crate::DB_NAME);
+    let token_collection = db.collection::<TokenDB>(super::TOKEN_TABLE);
+
+    let query = doc!{ "_id": token_db_id };
+    let update = doc!{
+        "$set": {
+            "burned": true,
+            "current_owner": Bson::Null,
+            "burned_at_height": history_entry.block_height.map(|block_height| block_height as i64),
+            "burn_receipt_id": history_entry.receipt_id.clone(),
+            "burn_authorized_id": history_entry.authorized_id.clone(),
+        },
+        "$push": { "ownership_history": mongodb::bson::to_bson(&history_entry)? },
+    };
+
+    crate::await_retry_or_panic!(
+        token_collection.update_one(query.clone(), update.clone(), None),
+        10,
+        "Token burn was NOT added to database".to_string(),
+        (&contract_id, &token_id),
+    );
+
+    Ok(())
+}
+
+/// Appends the versions to the token and makes the metadata of the last one the latest
+pub(crate) async fn add_metadata_versions(
+    pool: &mongodb::Client,
+    contract_id: AccountId,
+    token_id: String,
+    metadata_versions: Vec<MetadataVersion>,
+) -> anyhow::Result<()> {
+
+    let metadata_version = match metadata_versions.last() {
+        Some(metadata_version) => metadata_version,
+        None => return Ok(()),
+    };
+
+    info!(
+        target: crate::INDEXER,
+        "Adding metadata versions of token {} in contract {}: {:#?}",
+        &token_id, &contract_id, &metadata_versions,
+    );
+
+    let token_db_id = utils::get_token_db_id(&contract_id, &token_id);
+
+    let db = pool.database(crate::DB_NAME);
+    let token_collection = db.collection::<TokenDB>(super::TOKEN_TABLE);
+
+    let query = doc!{ "_id": token_db_id };
+    let update = doc!{
+        "$set": { "metadata": mongodb::bson::to_bson(&metadata_version.metadata)? },
+        "$push": { "metadata_versions": { "$each": mongodb::bson::to_bson(&metadata_versions)? } },
+    };
+
+    crate::await_retry_or_panic!(
+        token_collection.update_one(query.clone(), update.clone(), None),
+        10,
+        "Metadata version was NOT added to database".to_string(),
+        (&contract_id, &token_id),
+    );
+
+    Ok(())
+}
+
+/// Time boundary of token metadata, in epoch milliseconds
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum TokenTimeField {
+    ExpiresAt,
+    StartsAt,
+}
+
+impl TokenTimeField {
+    fn metadata_field(&self) -> &'static str {
+        match self {
+            TokenTimeField::ExpiresAt => "metadata.expires_at",
+            TokenTimeField::StartsAt => "metadata.starts_at",
+        }
+    }
+
+    fn height_field(&self) -> &'static str {
+        match self {
+            TokenTimeField::ExpiresAt => "expired_at_height",
+            TokenTimeField::StartsAt => "activated_at_height",
+        }
+    }
+
+    fn retry_field(&self) -> &'static str {
+        match self {
+            TokenTimeField::ExpiresAt => "expire_retry",
+            TokenTimeField::StartsAt => "activate_retry",
+        }
+    }
+
+    fn retry_height_field(&self) -> &'static str {
+        match self {
+            TokenTimeField::ExpiresAt => "expire_retry.retry_at_height",
+            TokenTimeField::StartsAt => "activate_retry.retry_at_height",
+        }
+    }
+}
+
+/// Unburned tokens whose time boundary was crossed by `chain_time_ms` and not handled yet.
+/// Tokens whose notification was rejected are skipped until their retry height
+pub(crate) async fn get_tokens_due(
+    pool: &mongodb::Client,
+    field: TokenTimeField,
+    chain_time_ms: u64,
+    block_height: u64,
+    limit: i64,
+) -> anyhow::Result<Vec<TokenDB>> {
+
+    let db = pool.database(crate::DB_NAME);
+    let token_collection = db.collection::<TokenDB>(super::TOKEN_TABLE);
+
+    let query = doc!{
+        "metadata": { "$exists": true },
+        "burned": { "$ne": true },
+        field.metadata_field(): { "$lte": chain_time_ms as i64 },
+        field.height_field(): Bson::Null,
+        "$or": [
+            { field.retry_field(): Bson::Null },
+            { field.retry_height_field(): { "$lte": block_height as i64 } },
+        ],
+    };
+    let options = mongodb::options::FindOptions::builder()
+        .sort(doc!{ field.metadata_field(): 1 })
+        .limit(limit)
+        .build();
+
+    let cursor = crate::await_retry_or_panic!(
+        token_collection.find(query.clone(), options.clone()),
+        10,
+        "Due tokens were NOT read from database".to_string(),
+        &query,
+    );
+
+    match cursor {
+        Some(cursor) => Ok(cursor.try_collect().await?),
+        None => Ok(Vec::new()),
+    }
+}
+
+/// Records that chain time crossed the boundary, with a history entry unless the token was minted past it
+pub(crate) async fn mark_token_due(
+    pool: &mongodb::Client,
+    token_db_id: String,
+    field: TokenTimeField,
+    block_height: u64,
+    history_entry: Option<OwnershipHistoryEntry>,
+) -> anyhow::Result<()> {
+
+    info!(
+        target: crate::INDEXER,
+        "Marking {} of token {} at height {}: {:#?}",
+        field.metadata_field(), &token_db_id, &block_height, &history_entry,
+    );
+
+    let db = pool.database(crate::DB_NAME);
+    let token_collection = db.collection::<TokenDB>(super::TOKEN_TABLE);
+
+    let query = doc!{ "_id": token_db_id.clone() };
+    let update = match history_entry {
+        Some(history_entry) => doc!{
+            "$set": { field.height_field(): block_height as i64 },
+            "$unset": { field.retry_field(): "" },
+            "$push": { "ownership_history": mongodb::bson::to_bson(&history_entry)? },
+        },
+        None => doc!{
+            "$set": { field.height_field(): block_height as i64 },
+            "$unset": { field.retry_field(): "" },
+        },
+    };
+
+    crate::await_retry_or_panic!(
+        token_collection.update_one(query.clone(), update.clone(), None),
+        10,
+        "Token time event was NOT added to database".to_string(),
+        &token_db_id,
+    );
+
+    Ok(())
+}
+
+/// Postpones the boundary of a token whose notification was rejected
+pub(crate) async fn record_notify_retry(
+    pool: &mongodb::Client,
+    token_db_id: String,
+    field: TokenTimeField,
+    retry: NotifyRetry,
+) -> anyhow::Result<()> {
+
+    info!(
+        target: crate::INDEXER,
+        "Postponing {} of token {}: {:?}",
+        field.metadata_field(), &token_db_id, &retry,
+    );
+
+    let db = pool.database(crate::DB_NAME);
+    let token_collection = db.collection::<TokenDB>(super::TOKEN_TABLE);
+
+    let query = doc!{ "_id": token_db_id.clone() };
+    let update = doc!{ "$set": { field.retry_field(): mongodb::bson::to_bson(&retry)? } };
+
+    crate::await_retry_or_panic!(
+        token_collection.update_one(query.clone(), update.clone(), None),
+        10,
+        "Token notification retry was NOT updated in database".to_string(),
+        &token_db_id,
+    );
+
+    Ok(())
+}
+
+pub(crate) async fn get_token(
+    pool: &mongodb::Client,
+    contract_id: AccountId,
+    token_id: String,
+) -> anyhow::Result<Option<TokenDB>> {
+
+    let token_db_id = utils::get_token_db_id(&contract_id, &token_id);
+
+    let db = pool.database(crate::DB_NAME);
+    let token_collection = db.collection::<TokenDB>(super::TOKEN_TABLE);
+
+    // Transfers of tokens minted before indexing create documents with only ids and an ownership history,
+    // minted tokens always have a metadata field
+    let query = doc!{ "_id": token_db_id, "metadata": { "$exists": true } };
+
+    let token = crate::await_retry_or_panic!(
+        token_collection.find_one(query.clone(), None),
+        10,
+        "Token was NOT read from database".to_string(),
+        (&contract_id, &token_id),
+    );
+
+    Ok(token.flatten())
+}
+
+/// Tokens matching the words of `text` in title, description or collection name, best matches first
+pub(crate) async fn search_tokens(
+    pool: &mongodb::Client,
+    text: String,
+    contract_id: Option<AccountId>,
+    game_id: Option<String>,
+    limit: i64,
+) -> anyhow::Result<Vec<TokenDB>> {
+
+    let db = pool.database(crate::DB_NAME);
+    let token_collection = db.collection::<TokenDB>(super::TOKEN_TABLE);
+
+    let mut query = doc!{ "$text": { "$search": text.clone() }, "metadata": { "$exists": true } };
+    if let Some(contract_id) = contract_id {
+        query.insert("contract_id", contract_id.to_string());
+    }
+    if let Some(game_id) = game_id {
+        query.insert("metadata.game_id", game_id);
+    }
+    let options = mongodb::options::FindOptions::builder()
+        .projection(doc!{ "search_score": { "$meta": "textScore" } })
+        .sort(doc!{ "search_score": { "$meta": "textScore" } })
+        .limit(limit)
+        .build();
+
+    let cursor = crate::await_retry_or_panic!(
+        token_collection.find(query.clone(), options.clone()),
+        10,
+        "Tokens were NOT searched in database".to_string(),
+        &query,
+    );
+
+    match cursor {
+        Some(cursor) => Ok(cursor.try_collect().await?),
+        None => Ok(Vec::new()),
+    }
+}
+
+pub(crate) async fn get_token_by_legacy_id(
+    pool: &mongodb::Client,
+    legacy_id: String,
+) -> anyhow::Result<Option<TokenDB>> {
+
+    let db = pool.database(crate::DB_NAME);
+    let token_collection = db.collection::<TokenDB>(super::TOKEN_TABLE);
+
+    let query = doc!{ "legacy_id": legacy_id.clone(), "metadata": { "$exists": true } };
+
+    let token = crate::await_retry_or_panic!(
+        token_collection.find_one(query.clone(), None),
+        10,
+        "Token was NOT read from database".to_string(),
+        &legacy_id,
+    );
+
+    Ok(token.flatten())
+}
+
+
+/// Rewrites owner strings in `ownership_history` as structured entries and sets `current_owner` from them.
+/// The first owner is the minter when the token was stored at mint, else it is imported, and every following
+/// one received a transfer from the previous owner. Burn entries written with the burned owner as `owner`
+/// get it as `previous_owner` instead. Returns the number of migrated tokens
+pub(crate) async fn migrate_ownership_history(
+    pool: &mongodb::Client,
+) -> anyhow::Result<u64> {
+
+    let db = pool.database(crate::DB_NAME);
+    // Read as plain documents, transfers of tokens minted before indexing only have an ownership history
+    let token_collection = db.collection::<Document>(super::TOKEN_TABLE);
+
+    let query = doc!{ "$or": [
+        { "ownership_history": { "$type": "string" } },
+        { "ownership_history": { "$elemMatch": { "event": "burn", "owner": { "$type": "string" } } } },
+    ] };
+
+    let mut cursor = match crate::await_retry_or_panic!(
+        token_collection.find(query.clone(), None),
+        10,
+        "Tokens were NOT read from database".to_string(),
+        &query,
+    ) {
+        Some(cursor) => cursor,
+        None => return Ok(0),
+    };
+
+    let mut migrated = 0u64;
+
+    while let Some(token) = cursor.try_next().await? {
+
+        // Tokens only known from transfers have no contract id, their first owner didn't mint them while indexing
+        let first_event = match token.contains_key("contract_id") {
+            true => OwnershipEvent::Mint,
+            false => OwnershipEvent::Imported,
+        };
+
+        let mut previous_owner: Option<String> = None;
+        let mut history: Vec<OwnershipHistoryEntry> = Vec::new();
+
+        for record in token.get_array("ownership_history")? {
+            let mut entry = match record {
+                Bson::String(owner) => OwnershipHistoryEntry {
+                    event: if history.is_empty() { first_event.clone() } else { OwnershipEvent::Transfer },
+                    owner: Some(owner.clone()),
+                    previous_owner: previous_owner.clone(),
+                    block_height: None,
+                    block_timestamp: None,
+                    receipt_id: None,
+                    authorized_id: None,
+                    memo: None,
+                },
+                other => mongodb::bson::from_bson(other.clone())?,
+            };
+
+            if entry.event == OwnershipEvent::Burn && entry.owner.is_some() {
+                entry.previous_owner = entry.owner.take();
+            }
+
+            previous_owner = entry.owner.clone().or(previous_owner);
+            history.push(entry);
+        }
+
+        let token_id = token.get("_id").cloned().unwrap_or(Bson::Null);
+
+        info!(
+            target: crate::INDEXER,
+            "Migrating ownership history of {}: {:#?}",
+            &token_id, &history,
+        );
+
+        let query = doc!{ "_id": token_id.clone() };
+        let current_owner = history.last().and_then(|entry| entry.owner.clone());
+        let update = doc!{ "$set": { "ownership_history": mongodb::bson::to_bson(&history)?, "current_owner": current_owner } };
+
+        crate::await_retry_or_panic!(
+            token_collection.update_one(query.clone(), update.clone(), None),
+            10,
+            "Ownership history was NOT migrated".to_string(),
+            &token_id,
+        );
+
+        migrated += 1;
+    }
+
+    Ok(migrated)
+}
+
+
+/// Reparses metadata stored as plain strings before it was typed (`copies`, timestamps, hashes and `extra`),
+/// which can't be read as `TokenMetadata` anymore. Returns the number of migrated tokens
+pub(crate) async fn migrate_token_metadata(
+    pool: &mongodb::Client,
+) -> anyhow::Result<u64> {
+
+    let db = pool.database(crate::DB_NAME);
+    let token_collection = db.collection::<Document>(super::TOKEN_TABLE);
+
+    // Typed metadata always has `parse_errors`
+    let query = doc!{ "metadata": { "$type": "object" }, "metadata.parse_errors": { "$exists": false } };
+
+    let mut cursor = match crate::await_retry_or_panic!(
+        token_collection.find(query.clone(), None),
+        10,
+        "Tokens were NOT read from database".to_string(),
+        &query,
+    ) {
+        Some(cursor) => cursor,
+        None => return Ok(0),
+    };
+
+    let mut migrated = 0u64;
+
+    while let Some(token) = cursor.try_next().await? {
+
+        let token_id = token.get("_id").cloned().unwrap_or(Bson::Null);
+        let legacy_metadata = Bson::Document(token.get_document("metadata")?.clone()).into_relaxed_extjson();
+        let metadata = TokenMetadata::from_json(Some(&legacy_metadata));
+
+        info!(
+            target: crate::INDEXER,
+            "Migrating metadata of {}: {:#?}",
+            &token_id, &metadata,
+        );
+
+        let query = doc!{ "_id": token_id.clone() };
+        let update = doc!{ "$set": { "metadata": mongodb::bson::to_bson(&metadata)? } };
+
+        crate::await_retry_or_panic!(
+            token_collection.update_one(query.clone(), update.clone(), None),
+            10,
+            "Token metadata was NOT migrated".to_string(),
+            &token_id,
+        );
+
+        migrated += 1;
+    }
+
+    Ok(migrated)
+}
+
+/// Holder of the token after the history entries up to the block height, `None` if not minted yet or burned.
+/// Migrated entries without a block height are assumed to precede it
+pub(crate) fn get_owner_at_height(
+    ownership_history: &[OwnershipHistoryRecord],
+    block_height: u64,
+) -> Option<String> {
+
+    let mut owner: Option<String> = None;
+
+    for record in ownership_history {
+        match record {
+            OwnershipHistoryRecord::Entry(entry) => {
+                if entry.block_height.map(|entry_height| entry_height > block_height).unwrap_or(false) {
+                    break;
+                }
+                owner = entry.owner.clone();
+            },
+            OwnershipHistoryRecord::Legacy(legacy_owner) => owner = Some(legacy_owner.clone()),
+        }
+    }
+
+    owner
+}
+
+/// Token ids of the contract held by each wallet at the block height, optionally within a `collection_name`
+pub(crate) async fn get_holders_at_height(
+    pool: &mongodb::Client,
+    contract_id: AccountId,
+    collection_name: Option<String>,
+    block_height: u64,
+) -> anyhow::Result<BTreeMap<String, Vec<String>>> {
+
+    let db = pool.database(crate::DB_NAME);
+    let token_collection = db.collection::<Document>(super::TOKEN_TABLE);
+
+    let mut query = doc!{ "contract_id": contract_id.to_string() };
+    if let Some(collection_name) = collection_name {
+        query.insert("metadata.collection_name", collection_name);
+    }
+    let options = mongodb::options::FindOptions::builder()
+        .projection(doc!{ "token_id": 1, "ownership_history": 1 })
+        .build();
+
+    let mut holders: BTreeMap<String, Vec<String>> = BTreeMap::new();
+
+    let mut cursor = match crate::await_retry_or_panic!(
+        token_collection.find(query.clone(), options.clone()),
+        10,
+        "Tokens were NOT read from database".to_string(),
+        &query,
+    ) {
+        Some(cursor) => cursor,
+        None => return Ok(holders),
+    };
+
+    while let Some(token) = cursor.try_next().await? {
+        let token_id = token.get_str("token_id")?.to_string();
+        let ownership_history: Vec<OwnershipHistoryRecord> = match token.get("ownership_history") {
+            Some(ownership_history) => mongodb::bson::from_bson(ownership_history.clone())?,
+            None => Vec::new(),
+        };
+
+        if let Some(owner) = get_owner_at_height(&ownership_history, block_height) {
+            holders.entry(owner).or_default().push(token_id);
+        }
+    }
+
+    Ok(holders)
+}
+
+
+/// Outcome of `migrate_token_ids`
+#[derive(Debug, Serialize, Clone)]
+pub(crate) struct TokenIdMigration {
+    /// Legacy id and versioned id of every token migrated by this run
+    pub migrated_ids: Vec<(String, String)>,
+    /// Legacy id and versioned id of every token with a `legacy_id`, previous runs included
+    pub legacy_ids: Vec<(String, String)>,
+    pub migrated_wallets: u64,
+    pub migrated_approvals: u64,
+    /// Tokens without contract and token id, ex. transfers of tokens minted before indexing.
+    /// Their versioned id can't be derived from the hash
+    pub unmigrated_ids: Vec<String>,
+}
+
+/// Contract and token id of a token or approvals document that still has a legacy id
+fn get_legacy_document_ids(document: &Document) -> Option<(String, AccountId, String)> {
+    let legacy_id = document.get_str("_id").ok()?;
+
+    if utils::is_token_db_id(legacy_id) {
+        return None;
+    }
+
+    let contract_id = document.get_str("contract_id").ok()?.parse::<AccountId>().ok()?;
+    let token_id = document.get_str("token_id").ok()?;
+
+    Some((legacy_id.to_string(), contract_id, token_id.to_string()))
+}
+
+/// Ids can't be updated, so the document is stored under the new id before the old one is removed.
+/// A document already stored under the new id was written after the upgrade: its fields win, the ones it lacks are
+/// taken from the legacy document and its `appended_arrays` are appended to the legacy ones
+async fn move_document(
+    collection: &mongodb::Collection<Document>,
+    mut document: Document,
+    legacy_id: &str,
+    token_db_id: &str,
+    appended_arrays: &[&str],
+) -> anyhow::Result<()> {
+
+    let query = doc!{ "_id": token_db_id };
+
+    let existing = crate::await_retry_or_panic!(
+        collection.find_one(query.clone(), None),
+        10,
+        "Document under the new id was NOT read from database".to_string(),
+        (&legacy_id, &token_db_id),
+    );
+
+    if let Some(existing) = existing.flatten() {
+        for (key, value) in existing {
+            let value = match (value, document.get_array(&key)) {
+                (Bson::Array(appended), Ok(legacy)) if appended_arrays.contains(&key.as_str()) => {
+                    Bson::Array(legacy.iter().cloned().chain(appended).collect())
+                },
+                (value, _) => value,
+            };
+            document.insert(key, value);
+        }
+    }
+
+    document.insert("_id", token_db_id);
+
+    let options = mongodb::options::ReplaceOptions::builder().upsert(true).build();
+
+    crate::await_retry_or_panic!(
+        collection.replace_one(query.clone(), document.clone(), options.clone()),
+        10,
+        "Document was NOT stored under its new id".to_string(),
+        (&legacy_id, &token_db_id),
+    );
+
+    let query = doc!{ "_id": legacy_id };
+
+    crate::await_retry_or_panic!(
+        collection.delete_one(query.clone(), None),
+        10,
+        "Document was NOT removed from its legacy id".to_string(),
+        (&legacy_id, &token_db_id),
+    );
+
+    Ok(())
+}
+
+/// Moves tokens and approvals from keccak ids to versioned ids and rewrites the token lists of wallets.
+/// Tokens keep their previous id as `legacy_id`. Already migrated documents are skipped, so it can be run again
+pub(crate) async fn migrate_token_ids(
+    pool: &mongodb::Client,
+) -> anyhow::Result<TokenIdMigration> {
+
+    let db = pool.database(crate::DB_NAME);
+    let token_collection = db.collection::<Document>(super::TOKEN_TABLE);
+    let wallet_tokens_collection = db.collection::<Document>(super::WALLET_TOKENS);
+    let approvals_collection = db.collection::<Document>(super::TOKEN_APPROVALS);
+
+    let mut migration = TokenIdMigration {
+        migrated_ids: Vec::new(),
+        legacy_ids: Vec::new(),
+        migrated_wallets: 0,
+        migrated_approvals: 0,
+        unmigrated_ids: Vec::new(),
+    };
+
+    // Legacy tokens are read first, documents stored under new ids mustn't show up in the same cursor
+    let mut legacy_tokens: Vec<Document> = Vec::new();
+
+    if let Some(mut cursor) = crate::await_retry_or_panic!(
+        token_collection.find(None, None),
+        10,
+        "Tokens were NOT read from database".to_string(),
+        super::TOKEN_TABLE,
+    ) {
+        while let Some(token) = cursor.try_next().await? {
+            if !token.get_str("_id").map(utils::is_token_db_id).unwrap_or(true) {
+                legacy_tokens.push(token);
+            }
+        }
+    }
+
+    for mut token in legacy_tokens {
+        let (legacy_id, contract_id, token_id) = match get_legacy_document_ids(&token) {
+            Some(ids) => ids,
+            None => {
+                migration.unmigrated_ids.extend(token.get_str("_id").ok().map(|legacy_id| legacy_id.to_string()));
+                continue;
+            },
+        };
+
+        let token_db_id = utils::get_token_db_id(&contract_id, &token_id);
+
+        info!(
+            target: crate::INDEXER,
+            "Migrating token id {} to {}",
+            &legacy_id, &token_db_id,
+        );
+
+        token.insert("legacy_id", legacy_id.clone());
+        move_document(&token_collection, token, &legacy_id, &token_db_id, &["ownership_history"]).await?;
+
+        migration.migrated_ids.push((legacy_id, token_db_id));
+    }
+
+    // Wallets and the game backend may have missed the ids moved by an interrupted run
+    let query = doc!{ "legacy_id": { "$type": "string" } };
+    let options = mongodb::options::FindOptions::builder().projection(doc!{ "legacy_id": 1 }).build();
+
+    if let Some(mut cursor) = crate::await_retry_or_panic!(
+        token_collection.find(query.clone(), options.clone()),
+        10,
+        "Legacy ids were NOT read from database".to_string(),
+        &query,
+    ) {
+        while let Some(token) = cursor.try_next().await? {
+            if let (Ok(token_db_id), Ok(legacy_id)) = (token.get_str("_id"), token.get_str("legacy_id")) {
+                migration.legacy_ids.push((legacy_id.to_string(), token_db_id.to_string()));
+            }
+        }
+    }
+
+    let token_db_ids: HashMap<&str, &str> = migration.legacy_ids.iter()
+        .map(|(legacy_id, token_db_id)| (legacy_id.as_str(), token_db_id.as_str()))
+        .collect();
+
+    if let Some(mut cursor) = crate::await_retry_or_panic!(
+        wallet_tokens_collection.find(None, None),
+        10,
+        "Wallets were NOT read from database".to_string(),
+        super::WALLET_TOKENS,
+    ) {
+        while let Some(wallet) = cursor.try_next().await? {
+            let tokens: Vec<String> = wallet.get_array("tokens").cloned().unwrap_or_default()
+                .iter()
+                .filter_map(|token| token.as_str().map(|token| token.to_string()))
+                .collect();
+
+            // A token received again after the upgrade is listed under both ids
+            let mut migrated_tokens: Vec<String> = Vec::new();
+            for token in &tokens {
+                let token = token_db_ids.get(token.as_str()).map(|token_db_id| token_db_id.to_string()).unwrap_or(token.clone());
+                if !migrated_tokens.contains(&token) {
+                    migrated_tokens.push(token);
+                }
+            }
+
+            if migrated_tokens == tokens {
+                continue;
+            }
+
+            let owner = wallet.get("_id").cloned().unwrap_or(Bson::Null);
+            let query = doc!{ "_id": owner.clone() };
+            let update = doc!{ "$set": { "tokens": migrated_tokens } };
+
+            crate::await_retry_or_panic!(
+                wallet_tokens_collection.update_one(query.clone(), update.clone(), None),
+                10,
+                "Wallet tokens were NOT migrated".to_string(),
+                &owner,
+            );
+
+            migration.migrated_wallets += 1;
+        }
+    }
+
+    let mut legacy_approvals: Vec<Document> = Vec::new();
+
+    if let Some(mut cursor) = crate::await_retry_or_panic!(
+        approvals_collection.find(None, None),
+        10,
+        "Approvals were NOT read from database".to_string(),
+        super::TOKEN_APPROVALS,
+    ) {
+        while let Some(approvals) = cursor.try_next().await? {
+            if !approvals.get_str("_id").map(utils::is_token_db_id).unwrap_or(true) {
+                legacy_approvals.push(approvals);
+            }
+        }
+    }
+
+    for approvals in legacy_approvals {
+        if let Some((legacy_id, contract_id, token_id)) = get_legacy_document_ids(&approvals) {
+            let token_db_id = utils::get_token_db_id(&contract_id, &token_id);
+
+            move_document(&approvals_collection, approvals, &legacy_id, &token_db_id, &[]).await?;
+
+            migration.migrated_approvals += 1;
+        }
+    }
+
+    Ok(migration)
+}
+
+/// Tokens with a reference or media that wasn't resolved yet, or whose last resolution should be retried
+pub(crate) async fn get_unresolved_tokens(
+    pool: &mongodb::Client,
+    limit: i64,
+    max_attempts: u32,
+) -> anyhow::Result<Vec<TokenDB>> {
+
+    let db = pool.database(crate::DB_NAME);
+    let token_collection = db.collection::<TokenDB>(super::TOKEN_TABLE);
+
+    let query = doc!{
+        "metadata": { "$exists": true },
+        "$or": [
+            { "metadata.reference": { "$type": "string" } },
+            { "metadata.media": { "$type": "string" } },
+        ],
+        "resolution.complete": { "$ne": true },
+        "resolution.attempts": { "$not": { "$gte": max_attempts } },
+    };
+    let options = mongodb::options::FindOptions::builder().limit(limit).build();
+
+    let cursor = crate::await_retry_or_panic!(
+        token_collection.find(query.clone(), options.clone()),
+        10,
+        "Unresolved tokens were NOT read from database".to_string(),
+        &query,
+    );
+
+    match cursor {
+        Some(cursor) => Ok(cursor.try_collect().await?),
+        None => Ok(Vec::new()),
+    }
+}
+
+pub(crate) async fn store_token_resolution(
+    pool: &mongodb::Client,
+    token_db_id: String,
+    resolution: TokenResolution,
+) -> anyhow::Result<()> {
+
+    info!(
+        target: crate::INDEXER,
+        "Storing resolution of token {}: {:#?}",
+        &token_db_id, &resolution,
+    );
+
+    let db = pool.database(crate::DB_NAME);
+    let token_collection = db.collection::<TokenDB>(super::TOKEN_TABLE);
+
+    let query = doc!{ "_id": token_db_id.clone() };
+    let update = doc!{ "$set": { "resolution": mongodb::bson::to_bson(&resolution)? } };
+
+    crate::await_retry_or_panic!(
+        token_collection.update_one(query.clone(), update.clone(), None),
+        10,
+        "Token resolution was NOT updated in database".to_string(),
+        &token_db_id,
+    );
+
+    Ok(())
+}
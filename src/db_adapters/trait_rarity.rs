@@ -0,0 +1,222 @@
+use futures::TryStreamExt;
+use mongodb::bson::{ doc, Bson };
+use mongodb::options::{ FindOneAndUpdateOptions, FindOptions, ReturnDocument };
+use serde::{ Deserialize, Serialize };
+
+use tracing::info;
+use crate::utils;
+use super::holder_stats::{ CreditedHoldings, HolderCredit };
+use super::tokens::{ TokenDB, TraitRarity };
+
+
+/// Number of unburned tokens of a collection with the trait
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub(crate) struct TraitCountDB {
+    _id: String,
+    pub collection_id: String,
+    pub trait_type: String,
+    pub value: String,
+    pub count: i64,
+}
+
+/// Trait values are free-form, so the id hashes an unambiguous JSON array
+pub(crate) fn get_trait_id(collection_id: &str, trait_type: &str, value: &str) -> String {
+    utils::keccak256_hash_string(serde_json::json!([collection_id, trait_type, value]).to_string())
+}
+
+/// Changes the count of the trait and returns the new count
+pub(crate) async fn update_trait_count(
+    pool: &mongodb::Client,
+    collection_id: &str,
+    trait_type: &str,
+    value: &str,
+    change: i64,
+) -> anyhow::Result<i64> {
+
+    let db = pool.database(crate::DB_NAME);
+    let traits = db.collection::<TraitCountDB>(super::COLLECTION_TRAITS);
+
+    let query = doc!{ "_id": get_trait_id(collection_id, trait_type, value) };
+    let update = doc!{
+        "$inc": { "count": change },
+        "$setOnInsert": { "collection_id": collection_id, "trait_type": trait_type, "value": value },
+    };
+    let options = FindOneAndUpdateOptions::builder()
+        .upsert(true)
+        .return_document(ReturnDocument::After)
+        .build();
+
+    let trait_count = crate::await_retry_or_panic!(
+        traits.find_one_and_update(query.clone(), update.clone(), options.clone()),
+        10,
+        "Trait count was NOT updated in database".to_string(),
+        (&collection_id, &trait_type, &value),
+    );
+
+    Ok(trait_count.flatten().map(|trait_count| trait_count.count).unwrap_or(0))
+}
+
+/// Adds `change` to the score of every unburned token with the trait, except `token_db_id`.
+/// Holder credits follow, the credited holdings are returned for their leaderboard entries
+pub(crate) async fn adjust_trait_scores(
+    pool: &mongodb::Client,
+    trait_id: &str,
+    token_db_id: &str,
+    change: f64,
+) -> anyhow::Result<Vec<CreditedHoldings>> {
+
+    let db = pool.database(crate::DB_NAME);
+    let token_collection = db.collection::<TokenDB>(super::TOKEN_TABLE);
+
+    let query = doc!{
+        "trait_rarity.traits": trait_id,
+        "burned": { "$ne": true },
+        "_id": { "$ne": token_db_id },
+    };
+
+    let mut credited_query = query.clone();
+    credited_query.insert("holder_credit", doc!{ "$ne": Bson::Null });
+
+    let pipeline = vec![
+        doc!{ "$match": credited_query.clone() },
+        doc!{ "$group": {
+            "_id": { "contract_id": "$contract_id", "owner": "$holder_credit.owner", "game_id": "$holder_credit.game_id" },
+            "token_count": { "$sum": 1i64 },
+        } },
+        doc!{ "$project": { "_id": 0, "contract_id": "$_id.contract_id", "owner": "$_id.owner", "game_id": "$_id.game_id", "token_count": 1 } },
+    ];
+
+    let mut credited_holdings: Vec<CreditedHoldings> = Vec::new();
+
+    if let Some(mut cursor) = crate::await_retry_or_panic!(
+        token_collection.aggregate(pipeline.clone(), None),
+        10,
+        "Credited holdings were NOT read from database".to_string(),
+        (&trait_id, &token_db_id),
+    ) {
+        while let Some(holdings) = cursor.try_next().await? {
+            credited_holdings.push(mongodb::bson::from_document(holdings)?);
+        }
+    }
+
+    let update = doc!{ "$inc": { "trait_rarity.score": change } };
+
+    crate::await_retry_or_panic!(
+        token_collection.update_many(query.clone(), update.clone(), None),
+        10,
+        "Trait scores were NOT updated in database".to_string(),
+        (&trait_id, &change),
+    );
+
+    let update = doc!{ "$inc": { "holder_credit.rarity_score": change } };
+
+    crate::await_retry_or_panic!(
+        token_collection.update_many(credited_query.clone(), update.clone(), None),
+        10,
+        "Holder credits were NOT updated in database".to_string(),
+        (&trait_id, &change),
+    );
+
+    Ok(credited_holdings)
+}
+
+/// The holder credit of a credited token follows the score, its previous credit is returned
+pub(crate) async fn set_token_trait_rarity(
+    pool: &mongodb::Client,
+    token_db_id: &str,
+    trait_rarity: &TraitRarity,
+) -> anyhow::Result<Option<HolderCredit>> {
+
+    info!(
+        target: crate::INDEXER,
+        "Setting trait rarity of token {}: {:#?}",
+        &token_db_id, &trait_rarity,
+    );
+
+    let db = pool.database(crate::DB_NAME);
+    let token_collection = db.collection::<TokenDB>(super::TOKEN_TABLE);
+
+    let query = doc!{ "_id": token_db_id };
+    let update = doc!{ "$set": { "trait_rarity": mongodb::bson::to_bson(trait_rarity)? } };
+
+    let token = crate::await_retry_or_panic!(
+        token_collection.find_one_and_update(query.clone(), update.clone(), None),
+        10,
+        "Token trait rarity was NOT updated in database".to_string(),
+        &token_db_id,
+    );
+
+    let holder_credit = token.flatten().and_then(|token| token.holder_credit);
+
+    if holder_credit.is_some() {
+        let update = doc!{ "$set": { "holder_credit.rarity_score": trait_rarity.score } };
+
+        crate::await_retry_or_panic!(
+            token_collection.update_one(query.clone(), update.clone(), None),
+            10,
+            "Holder credit was NOT updated in database".to_string(),
+            &token_db_id,
+        );
+    }
+
+    Ok(holder_credit)
+}
+
+/// Unburned tokens of the collection, rarest first
+pub(crate) async fn get_ranked_tokens(
+    pool: &mongodb::Client,
+    collection_id: &str,
+    limit: i64,
+) -> anyhow::Result<Vec<TokenDB>> {
+
+    let db = pool.database(crate::DB_NAME);
+    let token_collection = db.collection::<TokenDB>(super::TOKEN_TABLE);
+
+    let query = doc!{ "trait_rarity.collection_id": collection_id, "burned": { "$ne": true } };
+    let options = FindOptions::builder()
+        .sort(doc!{ "trait_rarity.score": -1 })
+        .limit(limit)
+        .build();
+
+    let cursor = crate::await_retry_or_panic!(
+        token_collection.find(query.clone(), options.clone()),
+        10,
+        "Ranked tokens were NOT read from database".to_string(),
+        &query,
+    );
+
+    match cursor {
+        Some(cursor) => Ok(cursor.try_collect().await?),
+        None => Ok(Vec::new()),
+    }
+}
+
+/// Rank of the score within the collection, 1 being the rarest, and the number of ranked tokens
+pub(crate) async fn get_trait_rarity_rank(
+    pool: &mongodb::Client,
+    trait_rarity: &TraitRarity,
+) -> anyhow::Result<(u64, u64)> {
+
+    let db = pool.database(crate::DB_NAME);
+    let token_collection = db.collection::<TokenDB>(super::TOKEN_TABLE);
+
+    let ranked_query = doc!{ "trait_rarity.collection_id": trait_rarity.collection_id.clone(), "burned": { "$ne": true } };
+    let mut rarer_query = ranked_query.clone();
+    rarer_query.insert("trait_rarity.score", doc!{ "$gt": trait_rarity.score });
+
+    let rarer = crate::await_retry_or_panic!(
+        token_collection.count_documents(rarer_query.clone(), None),
+        10,
+        "Rarer tokens were NOT counted".to_string(),
+        &rarer_query,
+    );
+
+    let total = crate::await_retry_or_panic!(
+        token_collection.count_documents(ranked_query.clone(), None),
+        10,
+        "Ranked tokens were NOT counted".to_string(),
+        &ranked_query,
+    );
+
+    Ok((rarer.unwrap_or(0) + 1, total.unwrap_or(0)))
+}
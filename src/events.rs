@@ -7,6 +7,23 @@ use tracing::info;
 pub(crate) enum NearEvent {
     Nep141(Nep141Event),
     Nep171(Nep171Event),
+    Nep393(Nep393Event),
+    /// Any other NEP-297 event, mapped to storage by `configs::CustomEventSchema`
+    #[serde(skip)]
+    Custom(CustomEvent),
+}
+
+// *** Custom game standards ***
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub(crate) struct CustomEvent {
+    pub standard: String,
+    pub version: String,
+    pub event: String,
+    #[serde(default)]
+    pub data: serde_json::Value,
+    /// Index of the log within the outcome, set by `extract_events_from_logs`
+    #[serde(skip)]
+    pub log_index: usize,
 }
 
 // *** NEP-141 FT ***
@@ -65,6 +82,9 @@ pub(crate) enum Nep171EventKind {
     NftMint(Vec<NftMintData>),
     NftTransfer(Vec<NftTransferData>),
     NftBurn(Vec<NftBurnData>),
+    // NEP-171 v1.1.0
+    #[serde(alias = "nft_contract_metadata_update")]
+    ContractMetadataUpdate(Vec<NftContractMetadataUpdateData>),
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -91,19 +111,90 @@ pub(crate) struct NftBurnData {
     pub memo: Option<String>,
 }
 
-pub(crate) fn extract_events(
-    outcome: &near_indexer::IndexerExecutionOutcomeWithReceipt,
+#[derive(Serialize, Deserialize, Debug)]
+pub(crate) struct NftContractMetadataUpdateData {
+    pub memo: Option<String>,
+}
+
+// *** NEP-393 SBT ***
+#[derive(Serialize, Deserialize, Debug)]
+pub(crate) struct Nep393Event {
+    pub version: String,
+    #[serde(flatten)]
+    pub event_kind: Nep393EventKind,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(tag = "event", content = "data")]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum Nep393EventKind {
+    Mint(SbtMintData),
+    Recover(SbtRecoverData),
+    Renew(SbtRenewData),
+    Revoke(SbtRevokeData),
+    Burn(SbtBurnData),
+    Ban(Vec<String>),
+    SoulTransfer(SbtSoulTransferData),
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub(crate) struct SbtMintData {
+    /// Issuer contract
+    pub ctr: String,
+    /// Token ids minted for each owner
+    pub tokens: Vec<(String, Vec<u64>)>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub(crate) struct SbtRecoverData {
+    pub ctr: String,
+    pub old_owner: String,
+    pub new_owner: String,
+    pub tokens: Vec<u64>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub(crate) struct SbtRenewData {
+    pub ctr: String,
+    pub tokens: Vec<u64>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub(crate) struct SbtRevokeData {
+    pub ctr: String,
+    pub tokens: Vec<u64>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub(crate) struct SbtBurnData {
+    pub ctr: String,
+    pub tokens: Vec<u64>,
+    pub memo: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub(crate) struct SbtSoulTransferData {
+    pub from: String,
+    pub to: String,
+}
+
+pub(crate) fn extract_events_from_logs(
+    logs: &[String],
 ) -> Vec<NearEvent> {
     let prefix = "EVENT_JSON:";
-    outcome.execution_outcome.outcome.logs.iter().filter_map(|untrimmed_log| {
+    logs.iter().enumerate().filter_map(|(log_index, untrimmed_log)| {
         let log = untrimmed_log.trim();
         if !log.starts_with(prefix) {
             return None;
         }
 
-        match serde_json::from_str::<'_, NearEvent>(
-            log[prefix.len()..].trim(),
-        ) {
+        let event_json = log[prefix.len()..].trim();
+
+        match serde_json::from_str::<'_, NearEvent>(event_json)
+            .or_else(|err| serde_json::from_str::<'_, CustomEvent>(event_json)
+                .map(|event| NearEvent::Custom(CustomEvent { log_index, ..event }))
+                .map_err(|_| err))
+        {
             Ok(result) => Some(result),
             Err(err) => {
                 info!(
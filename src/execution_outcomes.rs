@@ -1,19 +1,31 @@
 use tracing::{ info, error, warn };
 use near_indexer::IndexerExecutionOutcomeWithReceipt;
-use near_indexer::near_primitives::{ types::AccountId, views::ExecutionStatusView, views::ReceiptEnumView };
+use near_indexer::near_primitives::{ types::AccountId, views::ActionView, views::BlockHeaderView, views::ExecutionStatusView, views::ReceiptEnumView };
 use std::collections::HashSet;
+use crate::configs::WatchConfig;
 use crate::functions;
-use crate::models::token;
+use crate::models::{ approval, contract, legacy, metadata, payout, storage, token };
+use crate::models::receipt_context::ReceiptContext;
 use crate::events;
+use crate::events::NearEvent;
+
+/// Function calls of the receipt and events of the outcome logs. Logs belong to the whole receipt, not to a single action,
+/// so both are extracted once per outcome and the events are correlated to the calls by `token::process_token_event`
+fn get_calls_and_events(
+    actions: &[ActionView],
+    logs: &[String],
+) -> (Vec<serde_json::Value>, Vec<NearEvent>) {
+    (functions::get_function_calls(actions), events::extract_events_from_logs(logs))
+}
 
 pub(crate) async fn process_execution_outcomes(
     pool: &mongodb::Client,
+    config: &WatchConfig,
+    block_header: &BlockHeaderView,
     execution_outcomes: &[IndexerExecutionOutcomeWithReceipt],
 ) {
     
-    let nft_contract_id: AccountId = AccountId::try_from(crate::NFT_CONTRACT_ID.to_string()).unwrap();
-    let ft_contract_id: AccountId = AccountId::try_from(crate::FT_CONTRACT_ID.to_string()).unwrap();
-    let watched_addresses: HashSet<AccountId> = HashSet::from([nft_contract_id,ft_contract_id]);
+    let watched_addresses: HashSet<AccountId> = config.watched_contracts();
 
     for execution_outcome in execution_outcomes {
     
@@ -30,31 +42,59 @@ pub(crate) async fn process_execution_outcomes(
 
             let receiver_id = &execution_outcome.receipt.receiver_id;
             let success = &execution_outcome.execution_outcome.outcome.status;
-            // Check 2: - SuccessValue
+            // Check 2: - SuccessValue, or SuccessReceiptId for legacy contracts whose calls returning a promise
+            // (ex. `nft_transfer_call`) have already applied their own changes and log no events
             match success {
-                ExecutionStatusView::SuccessValue(_) => {
+                ExecutionStatusView::SuccessValue(_) | ExecutionStatusView::SuccessReceiptId(_)
+                    if matches!(success, ExecutionStatusView::SuccessValue(_)) || config.is_legacy_contract(receiver_id) => {
                     
                     // Check 3: - FunctionCall
                     let receipt = &execution_outcome.receipt.receipt;
 
                     match receipt {
                         ReceiptEnumView::Action {
+                            signer_id,
                             actions,
                             ..
                         } => {
-                            for action in actions {
-                                
-                                if let Some(args) = functions::get_arg_from_function_call(&action) {
+                            let (function_calls, mut events) = get_calls_and_events(actions, &execution_outcome.execution_outcome.outcome.logs);
+
+                            if !function_calls.is_empty() {
+
+                                info!(
+                                    target: crate::INDEXER,
+                                    "Args serialized: {:#?}",
+                                    &function_calls,
+                                );
+
+                                let context = ReceiptContext {
+                                    contract_id: receiver_id.clone(),
+                                    receipt_id: execution_outcome.receipt.receipt_id.to_string(),
+                                    block_height: block_header.height,
+                                    block_timestamp: block_header.timestamp_nanosec,
+                                    predecessor_id: execution_outcome.receipt.predecessor_id.clone(),
+                                    signer_id: signer_id.clone(),
+                                    deposit: function_calls.iter().map(functions::get_deposit).sum(),
+                                    function_calls,
+                                    success_value: functions::get_success_value_json(success),
+                                };
+
+                                // Legacy contracts don't log events, so they are inferred from the calls
+                                if config.is_legacy_contract(receiver_id) {
+                                    events.extend(legacy::infer_legacy_events(pool, &context).await);
+                                }
 
-                                    info!(
-                                        target: crate::INDEXER,
-                                        "Args serialized: {:#?}",
-                                        &args,
-                                    );
+                                token::process_token_event(pool, config, &context, &events).await;
 
-                                    let events = events::extract_events(&execution_outcome);
+                                if config.is_nft_contract(receiver_id) {
+                                    contract::process_contract_init_calls(pool, &context).await;
+                                    approval::process_approval_calls(pool, &context).await;
+                                    payout::process_payout_calls(pool, &context).await;
+                                    metadata::process_metadata_update_calls(pool, config, &context).await;
+                                }
 
-                                    token::process_token_event(pool, receiver_id, &args, &events).await;
+                                if config.is_ft_contract(receiver_id) {
+                                    storage::process_storage_calls(pool, &context).await;
                                 }
                             }
                         },
@@ -75,4 +115,66 @@ pub(crate) async fn process_execution_outcomes(
             )
         }
     }
-}
\ No newline at end of file
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::Nep171EventKind;
+    use crate::models::token::{ get_token_args, MintArgsMatcher };
+
+    fn nft_mint(token_id: &str) -> ActionView {
+        ActionView::FunctionCall {
+            method_name: "nft_mint".to_string(),
+            args: serde_json::json!({ "token_id": token_id, "receiver_id": "alice.near", "metadata": { "title": token_id } }).to_string().into_bytes(),
+            gas: 30_000_000_000_000,
+            deposit: 0,
+        }
+    }
+
+    fn get_minted_token_ids(events: &[NearEvent]) -> Vec<&str> {
+        events.iter()
+            .filter_map(|event| match event {
+                NearEvent::Nep171(nep171event) => match &nep171event.event_kind {
+                    Nep171EventKind::NftMint(mints) => Some(mints),
+                    _ => None,
+                },
+                _ => None,
+            })
+            .flatten()
+            .flat_map(|mint| mint.token_ids.iter().map(|token_id| token_id.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn batched_receipt_events_are_extracted_once() {
+        let actions = vec![nft_mint("1"), nft_mint("2"), nft_mint("3")];
+        let logs = vec![
+            r#"EVENT_JSON:{"standard":"nep171","version":"1.0.0","event":"nft_mint","data":[{"owner_id":"alice.near","token_ids":["1","2"]}]}"#.to_string(),
+            "Minted".to_string(),
+            r#"EVENT_JSON:{"standard":"nep171","version":"1.0.0","event":"nft_mint","data":[{"owner_id":"alice.near","token_ids":["3"]}]}"#.to_string(),
+        ];
+
+        let (function_calls, events) = get_calls_and_events(&actions, &logs);
+
+        assert_eq!(function_calls.len(), 3);
+        // Not once per action
+        assert_eq!(events.len(), 2);
+
+        let minted_token_ids = get_minted_token_ids(&events);
+        assert_eq!(minted_token_ids, vec!["1", "2", "3"]);
+
+        // Every token is paired with the args of its own call, and only once
+        let token_args = get_token_args(&function_calls);
+        let mut mint_args = MintArgsMatcher::new(&token_args);
+
+        for token_id in minted_token_ids {
+            let args = mint_args.take(token_id).expect("token minted without args");
+            assert_eq!(args.token_id, Some(token_id));
+            assert_eq!(args.token_metadata.and_then(|metadata| metadata.get("title")), Some(&serde_json::json!(token_id)));
+        }
+
+        assert!(!mint_args.has_args());
+    }
+}
@@ -1,3 +1,5 @@
+use near_indexer::near_primitives::views::ExecutionStatusView;
+
 use crate::enums::ActionKind;
 
 pub(crate) fn get_arg_from_function_call(action: &near_indexer::near_primitives::views::ActionView) -> Option<serde_json::Value> {
@@ -10,4 +12,111 @@ pub(crate) fn get_arg_from_function_call(action: &near_indexer::near_primitives:
         },
         _ => None,
     }
-}
\ No newline at end of file
+}
+
+/// Serialized args of every FunctionCall action in a receipt, in the order the actions were batched
+pub(crate) fn get_function_calls(actions: &[near_indexer::near_primitives::views::ActionView]) -> Vec<serde_json::Value> {
+    actions.iter().filter_map(get_arg_from_function_call).collect()
+}
+
+pub(crate) fn get_method_name(function_call: &serde_json::Value) -> Option<&str> {
+    function_call.get("method_name").and_then(|method_name| method_name.as_str())
+}
+
+pub(crate) fn get_args_json(function_call: &serde_json::Value) -> Option<&serde_json::Value> {
+    function_call.get("args_json")
+}
+
+/// First of the named args that is a string
+pub(crate) fn get_string_arg(args: &serde_json::Value, names: &[&str]) -> Option<String> {
+    names.iter().find_map(|name| args.get(name).and_then(|value| value.as_str())).map(|value| value.to_string())
+}
+
+/// Decodes the base64 `SuccessValue` of an outcome as JSON. Empty and non-JSON values are ignored
+pub(crate) fn get_success_value_json(status: &ExecutionStatusView) -> Option<serde_json::Value> {
+    match status {
+        ExecutionStatusView::SuccessValue(value) => {
+            let bytes = base64::decode(value).ok()?;
+            serde_json::from_slice(&bytes).ok()
+        },
+        _ => None,
+    }
+}
+
+/// Attached deposit of the FunctionCall action in yoctoNEAR
+pub(crate) fn get_deposit(function_call: &serde_json::Value) -> u128 {
+    function_call.get("deposit")
+        .and_then(|deposit| deposit.as_str())
+        .and_then(|deposit| deposit.parse::<u128>().ok())
+        .unwrap_or(0)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use near_indexer::near_primitives::views::ActionView;
+
+    fn function_call(method_name: &str, args: &[u8], deposit: u128) -> ActionView {
+        ActionView::FunctionCall {
+            method_name: method_name.to_string(),
+            args: args.to_vec(),
+            gas: 30_000_000_000_000,
+            deposit,
+        }
+    }
+
+    #[test]
+    fn get_function_calls_keeps_batched_calls_in_order() {
+        let actions = vec![
+            function_call("nft_mint", br#"{"token_id":"1"}"#, 1),
+            ActionView::Transfer { deposit: 5 },
+            function_call("nft_approve", br#"{"token_id":"1","account_id":"market.near"}"#, 0),
+        ];
+
+        let function_calls = get_function_calls(&actions);
+
+        assert_eq!(function_calls.len(), 2);
+        assert_eq!(get_method_name(&function_calls[0]), Some("nft_mint"));
+        assert_eq!(get_method_name(&function_calls[1]), Some("nft_approve"));
+        assert_eq!(get_deposit(&function_calls[0]), 1);
+        assert_eq!(get_deposit(&function_calls[1]), 0);
+    }
+
+    #[test]
+    fn get_function_calls_skips_receipts_without_calls() {
+        let actions = vec![ActionView::CreateAccount, ActionView::Transfer { deposit: 5 }];
+
+        assert!(get_function_calls(&actions).is_empty());
+    }
+
+    #[test]
+    fn get_args_json_only_decodes_json_args() {
+        let actions = vec![
+            function_call("nft_mint", br#"{"token_id":"1"}"#, 0),
+            function_call("raw", &[0, 159, 146, 150], 0),
+        ];
+
+        let function_calls = get_function_calls(&actions);
+
+        assert_eq!(get_args_json(&function_calls[0]), Some(&serde_json::json!({ "token_id": "1" })));
+        assert_eq!(get_args_json(&function_calls[1]), None);
+    }
+
+    #[test]
+    fn get_string_arg_takes_the_first_named_string() {
+        let args = serde_json::json!({ "token_owner_id": "alice.near", "owner_id": "bob.near", "amount": 1 });
+
+        assert_eq!(get_string_arg(&args, &["receiver_id", "token_owner_id", "owner_id"]), Some("alice.near".to_string()));
+        assert_eq!(get_string_arg(&args, &["amount"]), None);
+    }
+
+    #[test]
+    fn get_success_value_json_decodes_base64_json() {
+        let status = ExecutionStatusView::SuccessValue(base64::encode(r#"{"payout":{}}"#));
+        assert_eq!(get_success_value_json(&status), Some(serde_json::json!({ "payout": {} })));
+
+        let status = ExecutionStatusView::SuccessValue(String::new());
+        assert_eq!(get_success_value_json(&status), None);
+    }
+}
@@ -1,6 +1,7 @@
 use std::collections::HashMap;
 use near_indexer::near_primitives::types::AccountId;
 use crate::utils;
+use crate::db_adapters::custom_events::CustomEventDB;
 
 pub(crate) async fn mint_game_asset(
     contract_id: AccountId,
@@ -9,7 +10,7 @@ pub(crate) async fn mint_game_asset(
     let mut url: String = crate::SERVER_BASE_URL.clone().to_owned();
     url.push_str("mintGameAsset");
 
-    let token_db_id = utils::keccak256_hash_string(format!("{}{}", contract_id, token_id));
+    let token_db_id = utils::get_token_db_id(&contract_id, &token_id);
 
     let params = [("contract_id", contract_id.to_string()), ("token_id", token_id), ("near_tokend_db_id", token_db_id)];
 
@@ -27,6 +28,126 @@ pub(crate) async fn mint_game_asset(
     Ok(())
 }
 
+pub(crate) async fn burn_game_asset(
+    contract_id: AccountId,
+    token_id: String,
+) -> anyhow::Result<()> {
+    let mut url: String = crate::SERVER_BASE_URL.clone().to_owned();
+    url.push_str("burnGameAsset");
+
+    let token_db_id = utils::get_token_db_id(&contract_id, &token_id);
+
+    let params = [("contract_id", contract_id.to_string()), ("token_id", token_id), ("near_tokend_db_id", token_db_id)];
+
+    let args = HashMap::from(params);
+
+    let client = reqwest::Client::new();
+
+    crate::await_retry_or_panic!(
+        client.post(url.clone()).json(&args).send(),
+        10,
+        "Burn request to gg-backend failed".to_string(),
+        &args,
+    );
+
+    Ok(())
+}
+
+pub(crate) async fn expire_game_asset(
+    contract_id: AccountId,
+    token_id: String,
+    owner_id: Option<String>,
+) -> anyhow::Result<()> {
+    let mut url: String = crate::SERVER_BASE_URL.clone().to_owned();
+    url.push_str("expireGameAsset");
+
+    let token_db_id = utils::get_token_db_id(&contract_id, &token_id);
+
+    let params = [("contract_id", Some(contract_id.to_string())), ("token_id", Some(token_id)), ("near_tokend_db_id", Some(token_db_id)), ("owner_id", owner_id)];
+
+    let args = HashMap::from(params);
+
+    let client = reqwest::Client::new();
+
+    let response = crate::await_retry_or_panic!(
+        client.post(url.clone()).json(&args).send(),
+        10,
+        "Expire request to gg-backend failed".to_string(),
+        &args,
+    );
+
+    // Rejected notifications are retried by the scheduler
+    if let Some(response) = response {
+        response.error_for_status()?;
+    }
+
+    Ok(())
+}
+
+pub(crate) async fn activate_game_asset(
+    contract_id: AccountId,
+    token_id: String,
+    owner_id: Option<String>,
+) -> anyhow::Result<()> {
+    let mut url: String = crate::SERVER_BASE_URL.clone().to_owned();
+    url.push_str("activateGameAsset");
+
+    let token_db_id = utils::get_token_db_id(&contract_id, &token_id);
+
+    let params = [("contract_id", Some(contract_id.to_string())), ("token_id", Some(token_id)), ("near_tokend_db_id", Some(token_db_id)), ("owner_id", owner_id)];
+
+    let args = HashMap::from(params);
+
+    let client = reqwest::Client::new();
+
+    let response = crate::await_retry_or_panic!(
+        client.post(url.clone()).json(&args).send(),
+        10,
+        "Activate request to gg-backend failed".to_string(),
+        &args,
+    );
+
+    // Rejected notifications are retried by the scheduler
+    if let Some(response) = response {
+        response.error_for_status()?;
+    }
+
+    Ok(())
+}
+
+/// Sends the versioned id of every token with a legacy id so the backend can rewrite its references.
+/// Ids of previous runs are sent again, the backend must accept known pairs
+pub(crate) async fn migrate_game_asset_ids(
+    migrated_ids: &[(String, String)],
+) -> anyhow::Result<()> {
+    let mut url: String = crate::SERVER_BASE_URL.clone().to_owned();
+    url.push_str("migrateGameAssetIds");
+
+    let client = reqwest::Client::new();
+
+    for batch in migrated_ids.chunks(100) {
+        let ids: Vec<HashMap<&str, &String>> = batch.iter()
+            .map(|(legacy_id, token_db_id)| HashMap::from([("legacy_db_id", legacy_id), ("near_tokend_db_id", token_db_id)]))
+            .collect();
+
+        let args = HashMap::from([("ids", ids)]);
+
+        let response = crate::await_retry_or_panic!(
+            client.post(url.clone()).json(&args).send(),
+            10,
+            "Id migration request to gg-backend failed".to_string(),
+            &args,
+        );
+
+        // Stops at the first rejected batch, the migration can be run again
+        if let Some(response) = response {
+            response.error_for_status()?;
+        }
+    }
+
+    Ok(())
+}
+
 pub(crate) async fn transfer_ft(
     from_wallet_id: String,
     to_wallet_id: String,
@@ -54,4 +175,80 @@ pub(crate) async fn transfer_ft(
     );
 
     Ok(())
-}
\ No newline at end of file
+}
+
+pub(crate) async fn notify_custom_event(
+    endpoint: &str,
+    record: &CustomEventDB,
+) -> anyhow::Result<()> {
+    let mut url: String = crate::SERVER_BASE_URL.clone().to_owned();
+    url.push_str(endpoint);
+
+    let client = reqwest::Client::new();
+
+    crate::await_retry_or_panic!(
+        client.post(url.clone()).json(record).send(),
+        10,
+        "Custom event request to gg-backend failed".to_string(),
+        record,
+    );
+
+    Ok(())
+}
+
+pub(crate) async fn mint_soulbound_token(
+    registry_id: AccountId,
+    issuer: String,
+    token_id: u64,
+    owner: String,
+    class: Option<u64>,
+) -> anyhow::Result<()> {
+    let mut url: String = crate::SERVER_BASE_URL.clone().to_owned();
+    url.push_str("mintSoulboundToken");
+
+    let params = [
+        ("registry_id", Some(registry_id.to_string())),
+        ("issuer", Some(issuer)),
+        ("token_id", Some(token_id.to_string())),
+        ("owner", Some(owner)),
+        ("class", class.map(|class| class.to_string())),
+    ];
+    let args = HashMap::from(params);
+
+    let client = reqwest::Client::new();
+
+    crate::await_retry_or_panic!(
+        client.post(url.clone()).json(&args).send(),
+        10,
+        "Soulbound mint request to gg-backend failed".to_string(),
+        &args,
+    );
+
+    Ok(())
+}
+
+pub(crate) async fn revoke_soulbound_tokens(
+    registry_id: AccountId,
+    issuer: String,
+    token_ids: Vec<u64>,
+) -> anyhow::Result<()> {
+    let mut url: String = crate::SERVER_BASE_URL.clone().to_owned();
+    url.push_str("revokeSoulboundTokens");
+
+    let args = serde_json::json!({
+        "registry_id": registry_id,
+        "issuer": issuer,
+        "token_ids": token_ids,
+    });
+
+    let client = reqwest::Client::new();
+
+    crate::await_retry_or_panic!(
+        client.post(url.clone()).json(&args).send(),
+        10,
+        "Soulbound revoke request to gg-backend failed".to_string(),
+        &args,
+    );
+
+    Ok(())
+}
@@ -2,11 +2,12 @@ use actix;
 
 use anyhow::Result;
 use clap::Parser;
-use tokio::sync::mpsc;
-use tracing::info;
+use tokio::sync::{ mpsc, watch };
+use near_indexer::near_primitives::views::BlockHeaderView;
+use tracing::{ info, warn };
 use tracing_subscriber::EnvFilter;
 
-use configs::{Opts, SubCommand};
+use configs::{Opts, SnapshotFormat, SubCommand};
 use near_indexer;
 
 mod configs;
@@ -21,6 +22,8 @@ mod models;
 mod db_adapters;
 mod utils;
 mod gg_adapters;
+mod metrics;
+mod api;
 #[macro_use]
 mod retriable;
 
@@ -41,9 +44,18 @@ const SERVER_BASE_URL: &str = "https://localhost:8080/";
 // Database
 const DB_NAME: &str = "testnet_db";
 
-async fn listen_blocks(mut stream: mpsc::Receiver<near_indexer::StreamerMessage>) {
+/// Block headers are handed to `models::scheduler::run_scheduler`, so time events don't hold up the stream
+async fn listen_blocks(
+    mut stream: mpsc::Receiver<near_indexer::StreamerMessage>,
+    block_headers: watch::Sender<Option<BlockHeaderView>>,
+) {
 
     let pool = models::get_mongo_client().await;
+    let config = configs::get_watch_config();
+
+    if let Err(error) = db_adapters::indexes::create_indexes(&pool).await {
+        warn!(target: INDEXER, "Error creating database indexes: {:?}", &error);
+    }
 
     while let Some(streamer_message) = stream.recv().await {
         // TODO: handle data as you need
@@ -297,9 +309,15 @@ async fn listen_blocks(mut stream: mpsc::Receiver<near_indexer::StreamerMessage>
                     &chunk,
                 );
 
-                execution_outcomes::process_execution_outcomes(&pool, &shard.receipt_execution_outcomes).await;
+                execution_outcomes::process_execution_outcomes(&pool, &config, &streamer_message.block.header, &shard.receipt_execution_outcomes).await;
             }
         }
+
+        if let Err(error) = block_headers.send(Some(streamer_message.block.header.clone())) {
+            warn!(target: INDEXER, "Error passing block to the scheduler: {:?}", &error);
+        }
+
+        metrics::set_indexed_block_height(streamer_message.block.header.height);
     }
 }
 
@@ -332,11 +350,79 @@ fn main() -> Result<()> {
             system.block_on(async move {
                 let indexer = near_indexer::Indexer::new(indexer_config).expect("Indexer::new()");
                 let stream = indexer.streamer();
-                actix::spawn(listen_blocks(stream));
+                let (block_headers, block_header_receiver) = watch::channel(None);
+                actix::spawn(listen_blocks(stream, block_headers));
+                actix::spawn(models::scheduler::run_scheduler(block_header_receiver));
+                actix::spawn(models::resolver::run_resolver());
+                let pool = models::get_mongo_client().await;
+                actix::spawn(api::start_server(pool).expect("Failed to start API server"));
             });
             system.run()?;
         }
         SubCommand::Init(config) => near_indexer::indexer_init_configs(&home_dir, config.into())?,
+        SubCommand::MigrateHistory => {
+            let system = actix::System::new();
+            let migrated = system.block_on(async move {
+                let pool = models::get_mongo_client().await;
+                db_adapters::tokens::migrate_ownership_history(&pool).await
+            })?;
+            info!(target: INDEXER, "Migrated ownership history of {} tokens", migrated);
+        }
+        SubCommand::MigrateMetadata => {
+            let system = actix::System::new();
+            let migrated = system.block_on(async move {
+                let pool = models::get_mongo_client().await;
+                db_adapters::tokens::migrate_token_metadata(&pool).await
+            })?;
+            info!(target: INDEXER, "Migrated metadata of {} tokens", migrated);
+        }
+        SubCommand::MigrateIds => {
+            let system = actix::System::new();
+            let migration = system.block_on(async move {
+                let pool = models::get_mongo_client().await;
+                let migration = db_adapters::tokens::migrate_token_ids(&pool).await?;
+                gg_adapters::migrate_game_asset_ids(&migration.legacy_ids).await?;
+                Ok::<_, anyhow::Error>(migration)
+            })?;
+            for legacy_id in &migration.unmigrated_ids {
+                warn!(target: INDEXER, "Token {} has no contract and token id, its id was NOT migrated", legacy_id);
+            }
+            info!(
+                target: INDEXER,
+                "Migrated ids of {} tokens, {} wallets and {} approvals",
+                migration.migrated_ids.len(), migration.migrated_wallets, migration.migrated_approvals,
+            );
+        }
+        SubCommand::BackfillLeaderboards => {
+            let system = actix::System::new();
+            let recredited = system.block_on(async move {
+                let pool = models::get_mongo_client().await;
+                models::leaderboard::backfill_holder_credits(&pool).await
+            })?;
+            info!(target: INDEXER, "Recredited {} tokens to leaderboards", recredited);
+        }
+        SubCommand::CheckConsistency => {
+            let system = actix::System::new();
+            let inconsistencies = system.block_on(async move {
+                let pool = models::get_mongo_client().await;
+                db_adapters::token_owners::check_owner_consistency(&pool).await
+            })?;
+            println!("{}", serde_json::to_string_pretty(&inconsistencies)?);
+            info!(target: INDEXER, "Found {} inconsistent tokens", inconsistencies.len());
+        }
+        SubCommand::Snapshot(args) => {
+            let format = args.format.clone();
+            let system = actix::System::new();
+            let snapshot = system.block_on(async move {
+                let pool = models::get_mongo_client().await;
+                models::snapshot::take_snapshot(&pool, args.contract, args.collection, args.height).await
+            })?;
+            match format {
+                SnapshotFormat::Csv => print!("{}", snapshot.to_csv()),
+                SnapshotFormat::Json => println!("{}", serde_json::to_string_pretty(&snapshot)?),
+            }
+            info!(target: INDEXER, "Found {} holders at height {}", snapshot.holders.len(), snapshot.block_height);
+        }
     }
     Ok(())
 }
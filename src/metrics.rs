@@ -0,0 +1,38 @@
+use std::sync::atomic::{ AtomicU64, Ordering };
+
+use crate::db_adapters::anomalies::AnomalyKind;
+
+#[allow(clippy::declare_interior_mutable_const)]
+const ZERO: AtomicU64 = AtomicU64::new(0);
+
+static ANOMALIES: [AtomicU64; AnomalyKind::ALL.len()] = [ZERO; AnomalyKind::ALL.len()];
+static INDEXED_BLOCK_HEIGHT: AtomicU64 = AtomicU64::new(0);
+
+pub(crate) fn inc_anomalies(kind: AnomalyKind) {
+    ANOMALIES[kind as usize].fetch_add(1, Ordering::Relaxed);
+}
+
+pub(crate) fn set_indexed_block_height(height: u64) {
+    INDEXED_BLOCK_HEIGHT.store(height, Ordering::Relaxed);
+}
+
+/// Metrics in Prometheus text format
+pub(crate) fn render() -> String {
+    let mut metrics = String::new();
+
+    metrics.push_str("# HELP indexer_block_height Height of the last indexed block\n");
+    metrics.push_str("# TYPE indexer_block_height gauge\n");
+    metrics.push_str(&format!("indexer_block_height {}\n", INDEXED_BLOCK_HEIGHT.load(Ordering::Relaxed)));
+
+    metrics.push_str("# HELP indexer_anomalies_total Indexed state anomalies detected while processing events\n");
+    metrics.push_str("# TYPE indexer_anomalies_total counter\n");
+    for kind in AnomalyKind::ALL {
+        metrics.push_str(&format!(
+            "indexer_anomalies_total{{kind=\"{}\"}} {}\n",
+            kind.as_str(),
+            ANOMALIES[kind as usize].load(Ordering::Relaxed),
+        ));
+    }
+
+    metrics
+}
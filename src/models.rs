@@ -3,6 +3,23 @@ use std::env;
 
 pub mod token;
 pub mod marketplace;
+pub mod custom_event;
+pub mod receipt_context;
+pub mod legacy;
+pub mod approval;
+pub mod payout;
+pub mod storage;
+pub mod soulbound;
+pub mod contract;
+pub mod anomaly;
+pub mod resolver;
+pub mod metadata;
+pub mod collection;
+pub mod rarity;
+pub mod snapshot;
+pub mod inventory;
+pub mod scheduler;
+pub mod leaderboard;
 
 /// Get database credentials from .env or fail
 pub(crate) fn get_database_credentials() -> String {
@@ -0,0 +1,26 @@
+use tracing::warn;
+
+use crate::db_adapters;
+use crate::db_adapters::anomalies::AnomalyDB;
+use crate::metrics;
+
+
+/// Counts the anomaly in metrics and stores it for investigation
+pub(crate) async fn report_anomaly(
+    pool: &mongodb::Client,
+    anomaly: AnomalyDB,
+) {
+
+    warn!(
+        target: crate::INDEXER,
+        "Anomaly {} on token {:?} of contract {}: expected {:?}, got {:?}",
+        anomaly.kind.as_str(), &anomaly.token_id, &anomaly.contract_id, &anomaly.expected, &anomaly.actual,
+    );
+
+    metrics::inc_anomalies(anomaly.kind);
+
+    match db_adapters::anomalies::store_anomaly(&pool, anomaly).await {
+        Err(error) => warn!( target: crate::INDEXER, "Error adding anomaly to database: {:?}", &error ),
+        _ => (),
+    }
+}
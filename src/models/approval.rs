@@ -0,0 +1,94 @@
+use serde_json::value::Value;
+use tracing::{ info, warn };
+
+use crate::functions;
+use crate::db_adapters;
+use crate::models::receipt_context::ReceiptContext;
+
+
+/// Indexes NEP-178 `nft_approve`, `nft_revoke` and `nft_revoke_all` calls of the receipt
+pub(crate) async fn process_approval_calls(
+    pool: &mongodb::Client,
+    context: &ReceiptContext,
+) {
+
+    for (index, function_call) in context.function_calls.iter().enumerate() {
+
+        let (method_name, args) = match (functions::get_method_name(function_call), functions::get_args_json(function_call)) {
+            (Some(method_name), Some(args)) => (method_name, args),
+            _ => continue,
+        };
+
+        let token_id = match args.get("token_id").and_then(|token_id| token_id.as_str()) {
+            Some(token_id) => token_id.to_string(),
+            None => continue,
+        };
+
+        let result = match method_name {
+            "nft_approve" => {
+                let account_id = match args.get("account_id").and_then(|account_id| account_id.as_str()) {
+                    Some(account_id) => account_id.to_string(),
+                    None => continue,
+                };
+
+                info!(
+                    target: crate::INDEXER,
+                    "Approve {} for token {}",
+                    &account_id, &token_id,
+                );
+
+                db_adapters::approvals::add_approval(
+                    &pool,
+                    context.contract_id.clone(),
+                    token_id,
+                    // Only the owner can call `nft_approve`
+                    context.predecessor_id.to_string(),
+                    account_id,
+                    get_approval_id(args, context.get_success_value(index)),
+                    args.get("msg").and_then(|msg| msg.as_str()).map(|msg| msg.to_string()),
+                ).await
+            },
+            "nft_revoke" => {
+                let account_id = match args.get("account_id").and_then(|account_id| account_id.as_str()) {
+                    Some(account_id) => account_id.to_string(),
+                    None => continue,
+                };
+
+                db_adapters::approvals::remove_approval(&pool, context.contract_id.clone(), token_id, account_id).await
+            },
+            "nft_revoke_all" => db_adapters::approvals::clear_approvals(&pool, context.contract_id.clone(), token_id, None).await,
+            _ => continue,
+        };
+
+        match result {
+            Err(error) => warn!( target: crate::INDEXER, "Error updating approvals in database: {:?}", &error ),
+            _ => (),
+        }
+    }
+}
+
+
+/// Approval id reported by the contract, either in the args or as the returned value
+fn get_approval_id(
+    args: &Value,
+    success_value: Option<&Value>,
+) -> Option<u64> {
+    args.get("approval_id")
+        .or(success_value)
+        .and_then(|approval_id| approval_id.as_u64())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn approval_ids_are_read_from_args_or_the_returned_value() {
+        assert_eq!(get_approval_id(&json!({ "approval_id": 4 }), Some(&json!(9))), Some(4));
+        assert_eq!(get_approval_id(&json!({ "account_id": "market.near" }), Some(&json!(9))), Some(9));
+        assert_eq!(get_approval_id(&json!({ "account_id": "market.near" }), Some(&json!("9"))), None);
+        assert_eq!(get_approval_id(&json!({ "account_id": "market.near" }), None), None);
+    }
+}
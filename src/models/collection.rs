@@ -0,0 +1,86 @@
+use near_indexer::near_primitives::types::AccountId;
+use tracing::warn;
+
+use crate::db_adapters;
+use crate::db_adapters::collections::CollectionKey;
+use crate::db_adapters::tokens::TokenDB;
+use crate::models::receipt_context::ReceiptContext;
+use crate::models::token::{ Token, TokenMetadata };
+
+
+pub(crate) fn get_collection_key(
+    contract_id: &AccountId,
+    metadata: Option<&TokenMetadata>,
+) -> CollectionKey {
+    CollectionKey {
+        contract_id: contract_id.clone(),
+        collection_name: metadata.and_then(|metadata| metadata.collection_name.clone()),
+        game_id: metadata.and_then(|metadata| metadata.game_id.clone()),
+        rarity: metadata.and_then(|metadata| metadata.rarity.clone()),
+    }
+}
+
+/// Collection of an indexed token that isn't burned
+fn find_collection_key(
+    token: &TokenDB,
+) -> Option<CollectionKey> {
+
+    match token.burned {
+        true => None,
+        false => Some(get_collection_key(&token.contract_id, token.minted_metadata())),
+    }
+}
+
+
+/// Collection aggregates follow the minted metadata, later metadata updates don't move tokens between collections
+pub(crate) async fn record_mint(
+    pool: &mongodb::Client,
+    context: &ReceiptContext,
+    token: &Token,
+    owner_id: &str,
+) {
+
+    let key = get_collection_key(&token.contract_id, token.metadata.as_ref());
+
+    match db_adapters::collections::record_collection_mint(&pool, &key, owner_id.to_string(), context.block_height).await {
+        Err(error) => warn!( target: crate::INDEXER, "Error adding mint to collection: {:?}", &error ),
+        _ => (),
+    }
+}
+
+/// Must run before the transfer is applied. `old_owner_id` is the wallet the token is indexed in
+pub(crate) async fn record_transfer(
+    pool: &mongodb::Client,
+    token: &TokenDB,
+    old_owner_id: &str,
+    new_owner_id: &str,
+) {
+
+    let key = match find_collection_key(token) {
+        Some(key) => key,
+        None => return,
+    };
+
+    match db_adapters::collections::record_collection_transfer(&pool, &key, old_owner_id.to_string(), new_owner_id.to_string()).await {
+        Err(error) => warn!( target: crate::INDEXER, "Error adding transfer to collection: {:?}", &error ),
+        _ => (),
+    }
+}
+
+/// Must run before the burn is applied
+pub(crate) async fn record_burn(
+    pool: &mongodb::Client,
+    token: &TokenDB,
+    owner_id: &str,
+) {
+
+    let key = match find_collection_key(token) {
+        Some(key) => key,
+        None => return,
+    };
+
+    match db_adapters::collections::record_collection_burn(&pool, &key, owner_id.to_string()).await {
+        Err(error) => warn!( target: crate::INDEXER, "Error adding burn to collection: {:?}", &error ),
+        _ => (),
+    }
+}
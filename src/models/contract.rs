@@ -0,0 +1,104 @@
+use serde_json::value::Value;
+use tracing::{ info, warn };
+
+use crate::functions;
+use crate::db_adapters;
+use crate::db_adapters::contracts::ContractMetadata;
+use crate::models::receipt_context::ReceiptContext;
+
+
+/// Seeds the contract metadata from the `new` / `new_default_meta` init calls of the receipt
+pub(crate) async fn process_contract_init_calls(
+    pool: &mongodb::Client,
+    context: &ReceiptContext,
+) {
+
+    for function_call in &context.function_calls {
+
+        let (method_name, args) = match (functions::get_method_name(function_call), functions::get_args_json(function_call)) {
+            (Some(method_name), Some(args)) => (method_name, args),
+            _ => continue,
+        };
+
+        let metadata = match method_name {
+            "new" => match get_contract_metadata(args) {
+                Some(metadata) => Some(metadata),
+                None => continue,
+            },
+            "new_default_meta" => None,
+            _ => continue,
+        };
+
+        info!(
+            target: crate::INDEXER,
+            "Contract {} initialized with metadata: {:#?}",
+            &context.contract_id, &metadata,
+        );
+
+        let owner_id = args.get("owner_id").and_then(|owner_id| owner_id.as_str()).map(|owner_id| owner_id.to_string());
+
+        match db_adapters::contracts::init_contract(&pool, context.contract_id.clone(), owner_id, metadata, context.receipt_id.clone()).await {
+            Err(error) => warn!( target: crate::INDEXER, "Error adding contract to database: {:?}", &error ),
+            _ => (),
+        }
+    }
+}
+
+
+/// Refreshes the contract metadata after a `contract_metadata_update` event from the metadata
+/// passed to the call that logged it. Marks it stale when the call doesn't carry it
+pub(crate) async fn process_contract_metadata_update(
+    pool: &mongodb::Client,
+    context: &ReceiptContext,
+) {
+
+    let metadata = context.function_calls.iter()
+        .filter_map(functions::get_args_json)
+        .find_map(get_contract_metadata);
+
+    let result = match metadata {
+        Some(metadata) => {
+            info!(
+                target: crate::INDEXER,
+                "Contract {} metadata updated: {:#?}",
+                &context.contract_id, &metadata,
+            );
+
+            db_adapters::contracts::update_contract_metadata(&pool, context.contract_id.clone(), metadata, context.receipt_id.clone()).await
+        },
+        None => {
+            warn!(
+                target: crate::INDEXER,
+                "Contract {} metadata updated but the new metadata is not in the call args",
+                &context.contract_id,
+            );
+
+            db_adapters::contracts::mark_contract_metadata_stale(&pool, context.contract_id.clone(), context.receipt_id.clone()).await
+        },
+    };
+
+    match result {
+        Err(error) => warn!( target: crate::INDEXER, "Error updating contract in database: {:?}", &error ),
+        _ => (),
+    }
+}
+
+
+fn get_contract_metadata(args: &Value) -> Option<ContractMetadata> {
+    let metadata = args.get("metadata")?;
+
+    let get_string = |name: &str| metadata.get(name).and_then(|value| value.as_str()).map(|value| value.to_string());
+
+    // Token metadata is passed as `metadata` too, contract metadata always has a symbol
+    get_string("symbol")?;
+
+    Some(ContractMetadata {
+        spec: get_string("spec"),
+        name: get_string("name"),
+        symbol: get_string("symbol"),
+        icon: get_string("icon"),
+        base_uri: get_string("base_uri"),
+        reference: get_string("reference"),
+        reference_hash: get_string("reference_hash"),
+    })
+}
@@ -0,0 +1,127 @@
+use serde_json::value::Value;
+use tracing::{ info, warn };
+
+use crate::configs::{ CustomEventSchema, FieldType, WatchConfig };
+use crate::events::CustomEvent;
+use crate::db_adapters;
+use crate::db_adapters::custom_events::CustomEventDB;
+use crate::gg_adapters;
+use crate::models::metadata;
+use crate::models::receipt_context::ReceiptContext;
+
+
+pub(crate) async fn process_custom_event(
+    pool: &mongodb::Client,
+    config: &WatchConfig,
+    context: &ReceiptContext,
+    event: &CustomEvent,
+) {
+
+    let schema = match config.find_custom_event_schema(&event.standard, &event.event) {
+        Some(schema) => schema,
+        None => {
+            info!(
+                target: crate::INDEXER,
+                "No schema configured for event {} of standard {}. Ignoring",
+                &event.event, &event.standard,
+            );
+            return;
+        },
+    };
+
+    // NEP-297 data is usually an array of entries, store each entry as a separate record
+    let entries: Vec<&Value> = match &event.data {
+        Value::Array(entries) => entries.iter().collect(),
+        Value::Null => Vec::new(),
+        entry => vec![entry],
+    };
+
+    for (entry_index, entry) in entries.into_iter().enumerate() {
+
+        let fields = match extract_fields(schema, entry) {
+            Ok(fields) => fields,
+            Err(error) => {
+                warn!(
+                    target: crate::INDEXER,
+                    "Event {} of standard {} failed validation: {:?} \n{:#?}",
+                    &event.event, &event.standard, &error, &entry,
+                );
+                continue;
+            },
+        };
+
+        let record = CustomEventDB::new(context, event, entry_index, fields);
+
+        match db_adapters::custom_events::store_custom_event(&pool, &schema.collection, record.clone()).await {
+            Err(error) => warn!( target: crate::INDEXER, "Error adding custom event to database: {:?}", &error ),
+            _ => (),
+        }
+
+        if schema.metadata_update {
+            match record.fields.get("token_id").and_then(|token_id| token_id.as_str()) {
+                Some(token_id) => {
+                    let source = format!("{}:{}", &event.standard, &event.event);
+                    metadata::apply_metadata_update(pool, context, token_id.to_string(), record.fields.get("metadata"), source).await;
+                },
+                None => warn!(
+                    target: crate::INDEXER,
+                    "Metadata update event {} of standard {} has no token_id",
+                    &event.event, &event.standard,
+                ),
+            }
+        }
+
+        if let Some(endpoint) = &schema.endpoint {
+            match gg_adapters::notify_custom_event(endpoint, &record).await {
+                Err(error) => warn!( target: crate::INDEXER, "Error! Coudn't notify server: {:?}", &error),
+                _ => (),
+            }
+        }
+    }
+}
+
+
+/// Applies the schema field rules to a single event data entry.
+/// Without any rules the whole entry is kept
+fn extract_fields(
+    schema: &CustomEventSchema,
+    entry: &Value,
+) -> anyhow::Result<serde_json::Map<String, Value>> {
+
+    if schema.fields.is_empty() {
+        return match entry {
+            Value::Object(object_map) => Ok(object_map.clone()),
+            _ => Err(anyhow::anyhow!("Event data entry is not an object")),
+        };
+    }
+
+    let mut fields = serde_json::Map::new();
+
+    for rule in &schema.fields {
+        match entry.pointer(&rule.path) {
+            Some(Value::Null) | None => {
+                if rule.required {
+                    return Err(anyhow::anyhow!("Required field {} is missing at {}", rule.name, rule.path));
+                }
+            },
+            Some(value) => {
+                let type_matches = match rule.field_type {
+                    FieldType::Any => true,
+                    FieldType::String => value.is_string(),
+                    FieldType::Number => value.is_number(),
+                    FieldType::Bool => value.is_boolean(),
+                    FieldType::Object => value.is_object(),
+                    FieldType::Array => value.is_array(),
+                };
+
+                if !type_matches {
+                    return Err(anyhow::anyhow!("Field {} is not of type {:?}: {}", rule.name, rule.field_type, value));
+                }
+
+                fields.insert(rule.name.clone(), value.clone());
+            },
+        }
+    }
+
+    Ok(fields)
+}
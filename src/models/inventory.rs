@@ -0,0 +1,150 @@
+use near_indexer::near_primitives::types::AccountId;
+use tracing::warn;
+
+use crate::db_adapters;
+use crate::db_adapters::game_inventories::InventoryItem;
+use crate::db_adapters::tokens::TokenDB;
+use crate::models::token::TokenMetadata;
+
+
+/// Game and summary of the token, tokens without a `game_id` aren't in any inventory
+fn get_game_item(
+    contract_id: &AccountId,
+    token_id: &str,
+    metadata: Option<&TokenMetadata>,
+) -> Option<(String, InventoryItem)> {
+
+    let metadata = metadata?;
+    let game_id = metadata.game_id.clone()?;
+
+    Some((game_id, InventoryItem {
+        contract_id: contract_id.clone(),
+        token_id: token_id.to_string(),
+        title: metadata.title.clone(),
+        rarity: metadata.rarity.clone(),
+        nft_type: metadata.nft_type.clone(),
+    }))
+}
+
+async fn add_item(
+    pool: &mongodb::Client,
+    contract_id: &AccountId,
+    token_id: &str,
+    metadata: Option<&TokenMetadata>,
+    owner_id: &str,
+) {
+
+    let (game_id, item) = match get_game_item(contract_id, token_id, metadata) {
+        Some(game_item) => game_item,
+        None => return,
+    };
+
+    match db_adapters::game_inventories::add_inventory_item(&pool, game_id, owner_id.to_string(), item).await {
+        Err(error) => warn!( target: crate::INDEXER, "Error adding item to inventory: {:?}", &error ),
+        _ => (),
+    }
+}
+
+async fn remove_item(
+    pool: &mongodb::Client,
+    contract_id: &AccountId,
+    token_id: &str,
+    metadata: Option<&TokenMetadata>,
+    owner_id: &str,
+) {
+
+    let game_id = match metadata.and_then(|metadata| metadata.game_id.clone()) {
+        Some(game_id) => game_id,
+        None => return,
+    };
+
+    match db_adapters::game_inventories::remove_inventory_item(&pool, game_id, owner_id.to_string(), contract_id.clone(), token_id.to_string()).await {
+        Err(error) => warn!( target: crate::INDEXER, "Error removing item from inventory: {:?}", &error ),
+        _ => (),
+    }
+}
+
+/// Tokens without a `game_id` aren't in any inventory
+pub(crate) async fn record_mint(
+    pool: &mongodb::Client,
+    contract_id: &AccountId,
+    token_id: &str,
+    metadata: Option<&TokenMetadata>,
+    owner_id: &str,
+) {
+    add_item(pool, contract_id, token_id, metadata, owner_id).await;
+}
+
+/// `old_owner_id` is the wallet the token is indexed in
+pub(crate) async fn record_transfer(
+    pool: &mongodb::Client,
+    token: &TokenDB,
+    old_owner_id: &str,
+    new_owner_id: &str,
+) {
+    remove_item(pool, &token.contract_id, &token.token_id, token.metadata.as_ref(), old_owner_id).await;
+    add_item(pool, &token.contract_id, &token.token_id, token.metadata.as_ref(), new_owner_id).await;
+}
+
+pub(crate) async fn record_burn(
+    pool: &mongodb::Client,
+    token: &TokenDB,
+    owner_id: &str,
+) {
+    remove_item(pool, &token.contract_id, &token.token_id, token.metadata.as_ref(), owner_id).await;
+}
+
+/// Refreshes the summary of the token, moving it to another game when its `game_id` changed
+pub(crate) async fn record_metadata_update(
+    pool: &mongodb::Client,
+    token: &TokenDB,
+    metadata: &TokenMetadata,
+) {
+
+    let owner_id = match &token.current_owner {
+        Some(owner_id) if !token.burned => owner_id,
+        _ => return,
+    };
+
+    remove_item(pool, &token.contract_id, &token.token_id, token.metadata.as_ref(), owner_id).await;
+    add_item(pool, &token.contract_id, &token.token_id, Some(metadata), owner_id).await;
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn metadata(game_id: Option<&str>, title: &str) -> TokenMetadata {
+        TokenMetadata {
+            title: Some(title.to_string()),
+            rarity: Some("rare".to_string()),
+            game_id: game_id.map(|game_id| game_id.to_string()),
+            ..TokenMetadata::default()
+        }
+    }
+
+    #[test]
+    fn tokens_without_a_game_have_no_item() {
+        let contract_id: AccountId = "game.near".parse().unwrap();
+
+        assert!(get_game_item(&contract_id, "1", None).is_none());
+        assert!(get_game_item(&contract_id, "1", Some(&metadata(None, "Sword"))).is_none());
+    }
+
+    #[test]
+    fn metadata_updates_move_the_item_between_games() {
+        let contract_id: AccountId = "game.near".parse().unwrap();
+
+        let (old_game_id, _) = get_game_item(&contract_id, "1", Some(&metadata(Some("quest"), "Sword"))).unwrap();
+        let (new_game_id, item) = get_game_item(&contract_id, "1", Some(&metadata(Some("arena"), "Blade"))).unwrap();
+
+        assert_eq!(old_game_id, "quest");
+        assert_eq!(new_game_id, "arena");
+        assert_eq!(item.contract_id, contract_id);
+        assert_eq!(item.token_id, "1");
+        assert_eq!(item.title.as_deref(), Some("Blade"));
+        assert_eq!(item.rarity.as_deref(), Some("rare"));
+        assert_eq!(item.nft_type, None);
+    }
+}
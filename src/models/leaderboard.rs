@@ -0,0 +1,204 @@
+use near_indexer::near_primitives::types::AccountId;
+use tracing::warn;
+
+use crate::db_adapters;
+use crate::db_adapters::holder_stats::{ CreditedHoldings, HolderCredit, HolderScope };
+use crate::db_adapters::tokens::TokenDB;
+use crate::models::receipt_context::ReceiptContext;
+
+
+async fn update_holdings(
+    pool: &mongodb::Client,
+    contract_id: &AccountId,
+    holder_credit: &HolderCredit,
+    sign: i64,
+) -> anyhow::Result<()> {
+
+    let rarity_score = sign as f64 * holder_credit.rarity_score;
+
+    db_adapters::holder_stats::update_holder_stats(&pool, HolderScope::Contract, contract_id.to_string(), holder_credit.owner.clone(), sign, rarity_score).await?;
+
+    if let Some(game_id) = &holder_credit.game_id {
+        db_adapters::holder_stats::update_holder_stats(&pool, HolderScope::Game, game_id.clone(), holder_credit.owner.clone(), sign, rarity_score).await?;
+    }
+
+    Ok(())
+}
+
+fn get_holder_credit(
+    token: &TokenDB,
+    owner_id: &str,
+) -> HolderCredit {
+    HolderCredit {
+        owner: owner_id.to_string(),
+        game_id: token.metadata.as_ref().and_then(|metadata| metadata.game_id.clone()),
+        rarity_score: token.trait_rarity.as_ref().map(|trait_rarity| trait_rarity.score).unwrap_or(0f64),
+    }
+}
+
+/// Credits the token to the new holder with its current rarity score and game.
+/// The previous credit is debited as it was recorded, so later score or metadata changes can't skew the totals
+async fn move_holder_credit(
+    pool: &mongodb::Client,
+    token: &TokenDB,
+    new_owner_id: Option<&str>,
+) -> anyhow::Result<()> {
+
+    if let Some(holder_credit) = &token.holder_credit {
+        update_holdings(pool, &token.contract_id, holder_credit, -1).await?;
+    }
+
+    let holder_credit = new_owner_id.map(|new_owner_id| get_holder_credit(token, new_owner_id));
+
+    if let Some(holder_credit) = &holder_credit {
+        update_holdings(pool, &token.contract_id, holder_credit, 1).await?;
+    }
+
+    db_adapters::holder_stats::set_holder_credit(&pool, token.id().to_string(), holder_credit).await
+}
+
+
+/// Rescored tokens move the rarity of their holders by `change` each, their credits are already updated
+pub(crate) async fn record_score_change(
+    pool: &mongodb::Client,
+    credited_holdings: &[CreditedHoldings],
+    change: f64,
+) -> anyhow::Result<()> {
+
+    for holdings in credited_holdings {
+        let rarity_score_change = holdings.token_count as f64 * change;
+
+        db_adapters::holder_stats::update_holder_stats(&pool, HolderScope::Contract, holdings.contract_id.clone(), holdings.owner.clone(), 0, rarity_score_change).await?;
+
+        if let Some(game_id) = &holdings.game_id {
+            db_adapters::holder_stats::update_holder_stats(&pool, HolderScope::Game, game_id.clone(), holdings.owner.clone(), 0, rarity_score_change).await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Credits unburned tokens to their current holder where the credit is missing or stale, returns the number of
+/// recredited tokens. Covers tokens indexed before leaderboards or before credits followed score changes
+pub(crate) async fn backfill_holder_credits(
+    pool: &mongodb::Client,
+) -> anyhow::Result<u64> {
+
+    let tokens = db_adapters::holder_stats::get_miscredited_tokens(pool).await?;
+
+    for token in &tokens {
+        move_holder_credit(pool, token, token.current_owner.as_deref()).await?;
+    }
+
+    Ok(tokens.len() as u64)
+}
+
+/// Must run after the token is stored and scored
+pub(crate) async fn record_mint(
+    pool: &mongodb::Client,
+    context: &ReceiptContext,
+    token_id: &str,
+    owner_id: &str,
+) {
+
+    // Read back for the score given by `rarity::process_minted_token`
+    match db_adapters::tokens::get_token(&pool, context.contract_id.clone(), token_id.to_string()).await {
+        Ok(Some(token)) => match move_holder_credit(pool, &token, Some(owner_id)).await {
+            Err(error) => warn!( target: crate::INDEXER, "Error adding mint to leaderboards: {:?}", &error ),
+            _ => (),
+        },
+        Ok(None) => (),
+        Err(error) => warn!( target: crate::INDEXER, "Error reading token from database: {:?}", &error ),
+    }
+
+    match db_adapters::holder_stats::record_wallet_acquisition(&pool, owner_id.to_string(), true, context.block_height, context.block_timestamp).await {
+        Err(error) => warn!( target: crate::INDEXER, "Error adding mint to wallet stats: {:?}", &error ),
+        _ => (),
+    }
+}
+
+/// Tokens minted before indexing only count in wallet stats
+pub(crate) async fn record_transfer(
+    pool: &mongodb::Client,
+    context: &ReceiptContext,
+    token: Option<&TokenDB>,
+    old_owner_id: &str,
+    new_owner_id: &str,
+) {
+
+    match token {
+        Some(token) if !token.burned => match move_holder_credit(pool, token, Some(new_owner_id)).await {
+            Err(error) => warn!( target: crate::INDEXER, "Error adding transfer to leaderboards: {:?}", &error ),
+            _ => (),
+        },
+        _ => (),
+    }
+
+    let acquisition = db_adapters::holder_stats::record_wallet_acquisition(&pool, new_owner_id.to_string(), false, context.block_height, context.block_timestamp);
+    let transfer_sent = db_adapters::holder_stats::record_wallet_transfer_sent(&pool, old_owner_id.to_string());
+
+    match futures::try_join!(acquisition, transfer_sent) {
+        Err(error) => warn!( target: crate::INDEXER, "Error adding transfer to wallet stats: {:?}", &error ),
+        _ => (),
+    }
+}
+
+/// Must run before the burn is applied
+pub(crate) async fn record_burn(
+    pool: &mongodb::Client,
+    token: &TokenDB,
+) {
+
+    if token.burned {
+        return;
+    }
+
+    match move_holder_credit(pool, token, None).await {
+        Err(error) => warn!( target: crate::INDEXER, "Error adding burn to leaderboards: {:?}", &error ),
+        _ => (),
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn credits_follow_the_current_score_and_game() {
+        let token: TokenDB = serde_json::from_value(json!({
+            "_id": "v1:game.near:1",
+            "contract_id": "game.near",
+            "token_id": "1",
+            "metadata": { "game_id": "arena" },
+            "trait_rarity": { "collection_id": "game.near:", "attributes": [], "traits": [], "score": 1.5 },
+            "holder_credit": { "owner": "alice.near", "game_id": "quest", "rarity_score": 0.5 },
+        })).unwrap();
+
+        let holder_credit = get_holder_credit(&token, "bob.near");
+
+        assert_eq!(holder_credit.owner, "bob.near");
+        assert_eq!(holder_credit.game_id.as_deref(), Some("arena"));
+        assert_eq!(holder_credit.rarity_score, 1.5);
+
+        // The previous holder is debited as it was credited
+        let previous_credit = token.holder_credit.as_ref().unwrap();
+        assert_eq!(previous_credit.game_id.as_deref(), Some("quest"));
+        assert_eq!(previous_credit.rarity_score, 0.5);
+    }
+
+    #[test]
+    fn unscored_tokens_are_credited_without_rarity() {
+        let token: TokenDB = serde_json::from_value(json!({
+            "_id": "v1:game.near:1",
+            "contract_id": "game.near",
+            "token_id": "1",
+        })).unwrap();
+
+        let holder_credit = get_holder_credit(&token, "bob.near");
+
+        assert_eq!(holder_credit.game_id, None);
+        assert_eq!(holder_credit.rarity_score, 0f64);
+    }
+}
@@ -0,0 +1,170 @@
+use serde_json::value::Value;
+use tracing::info;
+
+use crate::events::{ NearEvent, Nep171Event, Nep171EventKind, NftMintData, NftTransferData, NftBurnData };
+use crate::functions;
+use crate::db_adapters;
+use crate::db_adapters::anomalies::{ AnomalyDB, AnomalyKind };
+use crate::models::anomaly;
+use crate::models::receipt_context::ReceiptContext;
+
+// Version reported by events inferred from function calls
+const LEGACY_EVENT_VERSION: &str = "legacy";
+
+
+/// Builds synthetic NEP-171 events for contracts that don't emit them,
+/// from the successful function calls of the receipt
+pub(crate) async fn infer_legacy_events(
+    pool: &mongodb::Client,
+    context: &ReceiptContext,
+) -> Vec<NearEvent> {
+
+    let mut events: Vec<NearEvent> = Vec::new();
+
+    for (index, function_call) in context.function_calls.iter().enumerate() {
+
+        let (method_name, args) = match (functions::get_method_name(function_call), functions::get_args_json(function_call)) {
+            (Some(method_name), Some(args)) => (method_name, args),
+            _ => continue,
+        };
+
+        let event_kind = match method_name {
+            "nft_transfer" | "nft_transfer_call" | "nft_transfer_payout" => infer_transfer(pool, context, args).await,
+            "nft_resolve_transfer" => infer_resolve_transfer(context.get_success_value(index), args),
+            "nft_mint" => infer_mint(pool, context, args).await,
+            "nft_burn" => infer_burn(pool, context, args).await,
+            _ => None,
+        };
+
+        if let Some(event_kind) = event_kind {
+
+            info!(
+                target: crate::INDEXER,
+                "Inferred {} event: {:#?}",
+                &method_name, &event_kind,
+            );
+
+            events.push(NearEvent::Nep171(Nep171Event {
+                version: LEGACY_EVENT_VERSION.to_string(),
+                event_kind,
+            }));
+        }
+    }
+
+    events
+}
+
+
+/// The caller is the owner unless indexed state says otherwise, in which case it is an approved account
+async fn get_owner_and_authorized_id(
+    pool: &mongodb::Client,
+    context: &ReceiptContext,
+    token_id: &str,
+) -> (String, Option<String>) {
+
+    let predecessor_id = context.predecessor_id.to_string();
+
+    let indexed_owner = db_adapters::token_owners::find_token_owner(pool, context.contract_id.clone(), token_id.to_string())
+        .await
+        .ok()
+        .flatten();
+
+    match indexed_owner {
+        Some(owner_id) if owner_id != predecessor_id => (owner_id, Some(predecessor_id)),
+        _ => (predecessor_id, None),
+    }
+}
+
+
+async fn infer_transfer(
+    pool: &mongodb::Client,
+    context: &ReceiptContext,
+    args: &Value,
+) -> Option<Nep171EventKind> {
+
+    let token_id = functions::get_string_arg(args, &["token_id"])?;
+    let new_owner_id = functions::get_string_arg(args, &["receiver_id"])?;
+    let (old_owner_id, authorized_id) = get_owner_and_authorized_id(pool, context, &token_id).await;
+
+    Some(Nep171EventKind::NftTransfer(vec![NftTransferData {
+        authorized_id,
+        old_owner_id,
+        new_owner_id,
+        token_ids: vec![token_id],
+        memo: functions::get_string_arg(args, &["memo"]),
+    }]))
+}
+
+
+/// `nft_resolve_transfer` returns `false` when the receiver refused the token and it went back to the owner
+fn infer_resolve_transfer(
+    success_value: Option<&Value>,
+    args: &Value,
+) -> Option<Nep171EventKind> {
+
+    if success_value != Some(&Value::Bool(false)) {
+        return None;
+    }
+
+    Some(Nep171EventKind::NftTransfer(vec![NftTransferData {
+        authorized_id: None,
+        old_owner_id: functions::get_string_arg(args, &["receiver_id"])?,
+        new_owner_id: functions::get_string_arg(args, &["owner_id"])?,
+        token_ids: vec![functions::get_string_arg(args, &["token_id"])?],
+        memo: None,
+    }]))
+}
+
+
+/// Contracts picking token ids themselves don't pass them in the args, without events those mints can't be indexed
+/// and are reported instead
+async fn infer_mint(
+    pool: &mongodb::Client,
+    context: &ReceiptContext,
+    args: &Value,
+) -> Option<Nep171EventKind> {
+
+    let owner_id = functions::get_string_arg(args, &["receiver_id", "token_owner_id", "owner_id"]);
+    let token_id = functions::get_string_arg(args, &["token_id"]);
+
+    match (owner_id, token_id) {
+        (Some(owner_id), Some(token_id)) => Some(Nep171EventKind::NftMint(vec![NftMintData {
+            owner_id,
+            token_ids: vec![token_id],
+            memo: functions::get_string_arg(args, &["memo"]),
+        }])),
+        (owner_id, token_id) => {
+            anomaly::report_anomaly(pool, AnomalyDB {
+                kind: AnomalyKind::UninferableMint,
+                contract_id: context.contract_id.clone(),
+                token_id,
+                receipt_id: context.receipt_id.clone(),
+                block_height: context.block_height,
+                block_timestamp: context.block_timestamp,
+                expected: None,
+                actual: owner_id,
+                details: args.clone(),
+            }).await;
+
+            None
+        },
+    }
+}
+
+
+async fn infer_burn(
+    pool: &mongodb::Client,
+    context: &ReceiptContext,
+    args: &Value,
+) -> Option<Nep171EventKind> {
+
+    let token_id = functions::get_string_arg(args, &["token_id"])?;
+    let (owner_id, authorized_id) = get_owner_and_authorized_id(pool, context, &token_id).await;
+
+    Some(Nep171EventKind::NftBurn(vec![NftBurnData {
+        authorized_id,
+        owner_id,
+        token_ids: vec![token_id],
+        memo: functions::get_string_arg(args, &["memo"]),
+    }]))
+}
@@ -0,0 +1,132 @@
+use serde_json::value::Value;
+use tracing::{ info, warn };
+
+use crate::configs::WatchConfig;
+use crate::functions;
+use crate::db_adapters;
+use crate::db_adapters::tokens::{ MetadataVersion, OwnershipEvent, OwnershipHistoryRecord, TokenDB };
+use crate::models::inventory;
+use crate::models::receipt_context::ReceiptContext;
+use crate::models::token::TokenMetadata;
+
+
+/// Indexes the metadata update calls of the receipt, see `WatchConfig::metadata_update_methods`
+pub(crate) async fn process_metadata_update_calls(
+    pool: &mongodb::Client,
+    config: &WatchConfig,
+    context: &ReceiptContext,
+) {
+
+    for function_call in &context.function_calls {
+
+        let (method_name, args) = match (functions::get_method_name(function_call), functions::get_args_json(function_call)) {
+            (Some(method_name), Some(args)) => (method_name, args),
+            _ => continue,
+        };
+
+        if !config.metadata_update_methods.iter().any(|update_method| update_method == method_name) {
+            continue;
+        }
+
+        // Single token or the same update for a batch of tokens
+        let token_ids: Vec<String> = match (args.get("token_id"), args.get("token_ids")) {
+            (Some(Value::String(token_id)), _) => vec![token_id.clone()],
+            (_, Some(Value::Array(token_ids))) => token_ids.iter().filter_map(|token_id| token_id.as_str()).map(|token_id| token_id.to_string()).collect(),
+            _ => Vec::new(),
+        };
+
+        for token_id in token_ids {
+            apply_metadata_update(pool, context, token_id, args.get("metadata"), method_name.to_string()).await;
+        }
+    }
+}
+
+
+/// Merges the update into the latest metadata of the token and stores it as a new version
+pub(crate) async fn apply_metadata_update(
+    pool: &mongodb::Client,
+    context: &ReceiptContext,
+    token_id: String,
+    metadata_update: Option<&Value>,
+    source: String,
+) {
+
+    let metadata_update = match TokenMetadata::from_json(metadata_update) {
+        Some(metadata_update) => metadata_update,
+        None => {
+            warn!(
+                target: crate::INDEXER,
+                "Metadata update of token {} from {} has no metadata",
+                &token_id, &source,
+            );
+            return;
+        },
+    };
+
+    let token = match db_adapters::tokens::get_token(&pool, context.contract_id.clone(), token_id.clone()).await {
+        Ok(Some(token)) => token,
+        Ok(None) => {
+            warn!(
+                target: crate::INDEXER,
+                "Metadata update of unknown token {} in contract {}",
+                &token_id, &context.contract_id,
+            );
+            return;
+        },
+        Err(error) => {
+            warn!( target: crate::INDEXER, "Error reading token from database: {:?}", &error );
+            return;
+        },
+    };
+
+    let metadata = token.metadata.clone().unwrap_or_default().merge(metadata_update);
+
+    // Tokens stored before versions were kept start their versions with the minted metadata
+    let mut metadata_versions: Vec<MetadataVersion> = match token.metadata_versions.is_empty() {
+        true => get_minted_version(&token).into_iter().collect(),
+        false => Vec::new(),
+    };
+
+    let metadata_version = MetadataVersion {
+        version: (token.metadata_versions.len() + metadata_versions.len()) as u32 + 1,
+        metadata,
+        block_height: Some(context.block_height),
+        receipt_id: Some(context.receipt_id.clone()),
+        source,
+    };
+
+    info!(
+        target: crate::INDEXER,
+        "Token {} metadata version {}",
+        &token_id, &metadata_version.version,
+    );
+
+    let metadata = metadata_version.metadata.clone();
+    metadata_versions.push(metadata_version);
+
+    match db_adapters::tokens::add_metadata_versions(&pool, context.contract_id.clone(), token_id, metadata_versions).await {
+        Err(error) => warn!( target: crate::INDEXER, "Error adding metadata version to database: {:?}", &error ),
+        _ => (),
+    }
+
+    inventory::record_metadata_update(pool, &token, &metadata).await;
+}
+
+
+/// Metadata the token was stored with, placed at the mint when it was indexed
+fn get_minted_version(token: &TokenDB) -> Option<MetadataVersion> {
+
+    let mint = token.ownership_history.iter()
+        .find_map(|record| match record {
+            OwnershipHistoryRecord::Entry(entry) if entry.event == OwnershipEvent::Mint => Some(entry),
+            _ => None,
+        });
+
+    Some(MetadataVersion {
+        version: 1,
+        metadata: token.metadata.clone()?,
+        block_height: mint.and_then(|mint| mint.block_height),
+        receipt_id: mint.and_then(|mint| mint.receipt_id.clone()),
+        source: "mint".to_string(),
+    })
+}
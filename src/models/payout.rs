@@ -0,0 +1,113 @@
+use std::collections::HashMap;
+use serde_json::value::Value;
+use tracing::{ info, warn };
+
+use crate::functions;
+use crate::db_adapters;
+use crate::db_adapters::royalties::{ PayoutDB, RoyaltyDistributionDB };
+use crate::models::receipt_context::ReceiptContext;
+
+
+/// Stores the NEP-199 payout returned by `nft_transfer_payout` calls of the receipt
+pub(crate) async fn process_payout_calls(
+    pool: &mongodb::Client,
+    context: &ReceiptContext,
+) {
+
+    for (index, function_call) in context.function_calls.iter().enumerate() {
+
+        let args = match (functions::get_method_name(function_call), functions::get_args_json(function_call)) {
+            (Some("nft_transfer_payout"), Some(args)) => args,
+            _ => continue,
+        };
+
+        process_payout_call(pool, context, args, context.get_success_value(index)).await;
+    }
+}
+
+
+async fn process_payout_call(
+    pool: &mongodb::Client,
+    context: &ReceiptContext,
+    args: &Value,
+    success_value: Option<&Value>,
+) {
+
+    let (token_id, receiver_id, balance) = match (functions::get_string_arg(args, &["token_id"]), functions::get_string_arg(args, &["receiver_id"]), functions::get_string_arg(args, &["balance"])) {
+        (Some(token_id), Some(receiver_id), Some(balance)) => (token_id, receiver_id, balance),
+        _ => {
+            warn!(
+                target: crate::INDEXER,
+                "Unexpected nft_transfer_payout args: {:#?}",
+                &args,
+            );
+            return;
+        },
+    };
+
+    // Only the last call of a batched receipt has its payout returned
+    let payout = match get_payout(success_value) {
+        Some(payout) => payout,
+        None => {
+            warn!(
+                target: crate::INDEXER,
+                "nft_transfer_payout didn't return a payout: {:#?}",
+                &success_value,
+            );
+            return;
+        },
+    };
+
+    let max_len_payout = match args.get("max_len_payout").and_then(|max_len_payout| max_len_payout.as_u64()).map(u32::try_from).transpose() {
+        Ok(max_len_payout) => max_len_payout,
+        Err(error) => {
+            warn!(
+                target: crate::INDEXER,
+                "Unexpected nft_transfer_payout max_len_payout: {}",
+                &error,
+            );
+            None
+        },
+    };
+
+    info!(
+        target: crate::INDEXER,
+        "Payout for token {}: {:#?}",
+        &token_id, &payout,
+    );
+
+    let game_id = match db_adapters::tokens::get_token(&pool, context.contract_id.clone(), token_id.clone()).await {
+        Ok(token) => token.and_then(|token| token.metadata).and_then(|metadata| metadata.game_id),
+        Err(error) => {
+            warn!( target: crate::INDEXER, "Error reading token from database: {:?}", &error );
+            None
+        },
+    };
+
+    let distribution = RoyaltyDistributionDB {
+        contract_id: context.contract_id.clone(),
+        token_id,
+        receipt_id: context.receipt_id.clone(),
+        game_id,
+        receiver_id,
+        approval_id: args.get("approval_id").and_then(|approval_id| approval_id.as_u64()),
+        memo: functions::get_string_arg(args, &["memo"]),
+        balance,
+        max_len_payout,
+        payout,
+    };
+
+    match db_adapters::royalties::store_royalty_distribution(&pool, distribution).await {
+        Err(error) => warn!( target: crate::INDEXER, "Error adding royalty distribution to database: {:?}", &error ),
+        _ => (),
+    }
+}
+
+
+/// `Payout` is returned as `{ "payout": { "<account_id>": "<U128>" } }`
+fn get_payout(success_value: Option<&Value>) -> Option<Vec<PayoutDB>> {
+    let payout = success_value?.get("payout")?;
+    let payout: HashMap<String, String> = serde_json::from_value(payout.clone()).ok()?;
+
+    Some(payout.into_iter().map(|(account_id, amount)| PayoutDB { account_id, amount }).collect())
+}
@@ -0,0 +1,197 @@
+use near_indexer::near_primitives::types::AccountId;
+use serde_json::value::Value;
+use tracing::{ info, warn };
+
+use crate::db_adapters;
+use crate::db_adapters::holder_stats::CreditedHoldings;
+use crate::db_adapters::tokens::{ TokenDB, TokenResolution, TokenTrait, TraitRarity };
+use crate::db_adapters::trait_rarity::get_trait_id;
+use crate::models::{ collection, leaderboard };
+use crate::models::token::{ Token, TokenMetadata };
+use crate::utils;
+
+
+/// Traits listed as `attributes` (or `traits`), either `[{ "trait_type", "value" }]` or `{ trait_type: value }`
+fn parse_traits(value: &Value) -> Vec<TokenTrait> {
+
+    let traits: Vec<TokenTrait> = match value.get("attributes").or(value.get("traits")) {
+        Some(Value::Array(attributes)) => attributes.iter()
+            .filter_map(|attribute| {
+                let trait_type = attribute.get("trait_type").or(attribute.get("type")).and_then(|trait_type| trait_type.as_str())?;
+                let value = attribute.get("value").and_then(get_trait_value)?;
+
+                Some(TokenTrait { trait_type: trait_type.to_string(), value })
+            })
+            .collect(),
+        Some(Value::Object(attributes)) => attributes.iter()
+            .filter_map(|(trait_type, value)| get_trait_value(value).map(|value| TokenTrait { trait_type: trait_type.clone(), value }))
+            .collect(),
+        _ => Vec::new(),
+    };
+
+    let mut unique_traits: Vec<TokenTrait> = Vec::new();
+    for token_trait in traits {
+        if !unique_traits.contains(&token_trait) {
+            unique_traits.push(token_trait);
+        }
+    }
+
+    unique_traits
+}
+
+fn get_trait_value(value: &Value) -> Option<String> {
+    match value {
+        Value::String(value) => Some(value.clone()),
+        Value::Number(_) | Value::Bool(_) => Some(value.to_string()),
+        _ => None,
+    }
+}
+
+
+/// Counts the traits of the minted token from its on-chain `extra`. Tokens without them are
+/// scored once their reference resolves, see `process_resolved_token`
+pub(crate) async fn process_minted_token(
+    pool: &mongodb::Client,
+    token: &Token,
+) {
+
+    let traits = match token.metadata.as_ref().and_then(|metadata| metadata.extra.as_ref()) {
+        Some(extra) => parse_traits(extra),
+        None => return,
+    };
+
+    let token_db_id = utils::get_token_db_id(&token.contract_id, &token.token_id);
+
+    score_token(pool, token_db_id, &token.contract_id, token.metadata.as_ref(), traits).await;
+}
+
+pub(crate) async fn process_resolved_token(
+    pool: &mongodb::Client,
+    token: &TokenDB,
+    resolution: &TokenResolution,
+) {
+
+    if token.trait_rarity.is_some() || token.burned {
+        return;
+    }
+
+    let traits = match resolution.reference.as_ref().and_then(|reference| reference.content.as_ref()) {
+        Some(content) => parse_traits(content),
+        None => return,
+    };
+
+    score_token(pool, token.id().to_string(), &token.contract_id, token.metadata.as_ref(), traits).await;
+}
+
+/// Must run before the burn is applied, the traits of the token stop counting in its collection
+pub(crate) async fn process_burned_token(
+    pool: &mongodb::Client,
+    token: &TokenDB,
+) {
+
+    if token.burned {
+        return;
+    }
+
+    if let Some(trait_rarity) = &token.trait_rarity {
+        match remove_trait_counts(pool, token.id().to_string(), trait_rarity).await {
+            Err(error) => warn!( target: crate::INDEXER, "Error removing token traits from database: {:?}", &error ),
+            _ => (),
+        }
+    }
+}
+
+
+async fn score_token(
+    pool: &mongodb::Client,
+    token_db_id: String,
+    contract_id: &AccountId,
+    metadata: Option<&TokenMetadata>,
+    attributes: Vec<TokenTrait>,
+) {
+
+    if attributes.is_empty() {
+        return;
+    }
+
+    let collection_id = collection::get_collection_key(contract_id, metadata).id();
+
+    info!(
+        target: crate::INDEXER,
+        "Scoring traits of token {} in collection {}: {:#?}",
+        &token_db_id, &collection_id, &attributes,
+    );
+
+    match add_trait_counts(pool, token_db_id, contract_id, collection_id, attributes).await {
+        Err(error) => warn!( target: crate::INDEXER, "Error adding token traits to database: {:?}", &error ),
+        _ => (),
+    }
+}
+
+/// A trait going from `count - 1` to `count` tokens lowers the score of the tokens sharing it
+/// from 1 / (count - 1) to 1 / count, so only those are updated
+async fn add_trait_counts(
+    pool: &mongodb::Client,
+    token_db_id: String,
+    contract_id: &AccountId,
+    collection_id: String,
+    attributes: Vec<TokenTrait>,
+) -> anyhow::Result<()> {
+
+    let mut traits: Vec<String> = Vec::new();
+    let mut score = 0f64;
+
+    for attribute in &attributes {
+        let trait_id = get_trait_id(&collection_id, &attribute.trait_type, &attribute.value);
+        let count = db_adapters::trait_rarity::update_trait_count(&pool, &collection_id, &attribute.trait_type, &attribute.value, 1).await?;
+
+        if count > 1 {
+            let change = 1f64 / count as f64 - 1f64 / (count - 1) as f64;
+            let credited_holdings = db_adapters::trait_rarity::adjust_trait_scores(&pool, &trait_id, &token_db_id, change).await?;
+            leaderboard::record_score_change(pool, &credited_holdings, change).await?;
+        }
+
+        score += 1f64 / count.max(1) as f64;
+        traits.push(trait_id);
+    }
+
+    let trait_rarity = TraitRarity {
+        collection_id,
+        attributes,
+        traits,
+        score,
+    };
+
+    // Tokens scored once their reference resolves are usually held already
+    if let Some(holder_credit) = db_adapters::trait_rarity::set_token_trait_rarity(&pool, &token_db_id, &trait_rarity).await? {
+        let credited_holdings = CreditedHoldings {
+            contract_id: contract_id.to_string(),
+            owner: holder_credit.owner,
+            game_id: holder_credit.game_id,
+            token_count: 1,
+        };
+        leaderboard::record_score_change(pool, &[credited_holdings], trait_rarity.score - holder_credit.rarity_score).await?;
+    }
+
+    Ok(())
+}
+
+async fn remove_trait_counts(
+    pool: &mongodb::Client,
+    token_db_id: String,
+    trait_rarity: &TraitRarity,
+) -> anyhow::Result<()> {
+
+    for attribute in &trait_rarity.attributes {
+        let trait_id = get_trait_id(&trait_rarity.collection_id, &attribute.trait_type, &attribute.value);
+        let count = db_adapters::trait_rarity::update_trait_count(&pool, &trait_rarity.collection_id, &attribute.trait_type, &attribute.value, -1).await?;
+
+        if count > 0 {
+            let change = 1f64 / count as f64 - 1f64 / (count + 1) as f64;
+            let credited_holdings = db_adapters::trait_rarity::adjust_trait_scores(&pool, &trait_id, &token_db_id, change).await?;
+            leaderboard::record_score_change(pool, &credited_holdings, change).await?;
+        }
+    }
+
+    Ok(())
+}
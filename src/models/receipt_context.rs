@@ -0,0 +1,42 @@
+use near_indexer::near_primitives::types::AccountId;
+
+use crate::functions;
+
+
+/// Chain context of the receipt whose execution outcome is being processed
+#[derive(Debug, Clone)]
+pub(crate) struct ReceiptContext {
+    pub contract_id: AccountId,
+    pub receipt_id: String,
+    pub block_height: u64,
+    /// Block timestamp in nanoseconds
+    pub block_timestamp: u64,
+    pub predecessor_id: AccountId,
+    /// Signer of the transaction that produced the receipt
+    pub signer_id: AccountId,
+    /// Attached deposit of every FunctionCall action together, in yoctoNEAR
+    pub deposit: u128,
+    /// Serialized args of every FunctionCall action, in the order the actions were batched
+    pub function_calls: Vec<serde_json::Value>,
+    /// JSON decoded `SuccessValue` of the outcome, if the contract returned any.
+    /// It is the value of the last action only, see `get_success_value`
+    pub success_value: Option<serde_json::Value>,
+}
+
+impl ReceiptContext {
+    /// Args JSON of the first FunctionCall action calling `method_name`
+    pub(crate) fn find_function_call_args(&self, method_name: &str) -> Option<&serde_json::Value> {
+        self.function_calls.iter()
+            .find(|function_call| functions::get_method_name(function_call) == Some(method_name))
+            .and_then(functions::get_args_json)
+    }
+
+    /// Value returned by the FunctionCall action at `index` of `function_calls`. A batched receipt only
+    /// returns the value of its last action, the values of the others aren't known
+    pub(crate) fn get_success_value(&self, index: usize) -> Option<&serde_json::Value> {
+        match index + 1 == self.function_calls.len() {
+            true => self.success_value.as_ref(),
+            false => None,
+        }
+    }
+}
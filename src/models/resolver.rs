@@ -0,0 +1,382 @@
+use dotenv::dotenv;
+use sha2::{ Digest, Sha256 };
+use std::env;
+use tracing::{ info, warn };
+
+use crate::db_adapters;
+use crate::db_adapters::tokens::{ ResolvedContent, TokenDB, TokenResolution, VerificationStatus };
+use crate::models::rarity;
+
+// Resolver constants
+const RESOLVER_INTERVAL: std::time::Duration = std::time::Duration::from_secs(10);
+const RESOLVER_BATCH_SIZE: i64 = 50;
+const RESOLVER_MAX_ATTEMPTS: u32 = 5;
+const FETCH_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(30);
+// Largest content fetched, references are kept in memory to be parsed while media is only hashed
+const MAX_REFERENCE_LENGTH: usize = 2 * 1024 * 1024;
+const MAX_MEDIA_LENGTH: usize = 50 * 1024 * 1024;
+
+// Default gateway for `ipfs://` URLs
+const IPFS_GATEWAY: &str = "https://ipfs.io/ipfs/";
+
+
+/// Content longer than the resolver accepts
+#[derive(Debug)]
+struct ContentTooLarge {
+    max_length: usize,
+}
+
+impl std::fmt::Display for ContentTooLarge {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Content is larger than {} bytes", self.max_length)
+    }
+}
+
+impl std::error::Error for ContentTooLarge {}
+
+struct FetchedContent {
+    content_type: Option<String>,
+    /// sha256 of the content
+    hash: Vec<u8>,
+    /// Only kept when requested
+    body: Option<Vec<u8>>,
+}
+
+
+/// Get IPFS gateway from .env or use the default
+fn get_ipfs_gateway() -> String {
+    dotenv().ok();
+
+    env::var("IPFS_GATEWAY").unwrap_or(IPFS_GATEWAY.to_string())
+}
+
+
+/// Resolves token references and media in the background, off the block processing path
+pub(crate) async fn run_resolver() {
+
+    let pool = crate::models::get_mongo_client().await;
+    let ipfs_gateway = get_ipfs_gateway();
+
+    let client = reqwest::Client::builder()
+        .timeout(FETCH_TIMEOUT)
+        .build()
+        .expect("Failed to build resolver HTTP client");
+
+    loop {
+        match db_adapters::tokens::get_unresolved_tokens(&pool, RESOLVER_BATCH_SIZE, RESOLVER_MAX_ATTEMPTS).await {
+            Ok(tokens) => {
+                for token in tokens {
+                    let resolution = resolve_token(&pool, &client, &ipfs_gateway, &token).await;
+
+                    match db_adapters::tokens::store_token_resolution(&pool, token.id().to_string(), resolution.clone()).await {
+                        Err(error) => warn!( target: crate::INDEXER, "Error storing token resolution in database: {:?}", &error ),
+                        _ => (),
+                    }
+
+                    // Tokens without on-chain traits are scored from their reference
+                    rarity::process_resolved_token(&pool, &token, &resolution).await;
+                }
+            },
+            Err(error) => warn!( target: crate::INDEXER, "Error reading unresolved tokens from database: {:?}", &error ),
+        }
+
+        tokio::time::sleep(RESOLVER_INTERVAL).await;
+    }
+}
+
+
+async fn resolve_token(
+    pool: &mongodb::Client,
+    client: &reqwest::Client,
+    ipfs_gateway: &str,
+    token: &TokenDB,
+) -> TokenResolution {
+
+    let metadata = token.metadata.clone().unwrap_or_default();
+
+    // Relative URLs are resolved against the contract `base_uri`
+    let base_uri = match db_adapters::contracts::get_contract(&pool, token.contract_id.clone()).await {
+        Ok(contract) => contract.and_then(|contract| contract.metadata).and_then(|metadata| metadata.base_uri),
+        Err(error) => {
+            warn!( target: crate::INDEXER, "Error reading contract from database: {:?}", &error );
+            None
+        },
+    };
+
+    let reference = match &metadata.reference {
+        Some(reference) => Some(resolve_content(client, ipfs_gateway, &base_uri, reference, &metadata.reference_hash, true).await),
+        None => None,
+    };
+
+    let media = match &metadata.media {
+        Some(media) => Some(resolve_content(client, ipfs_gateway, &base_uri, media, &metadata.media_hash, false).await),
+        None => None,
+    };
+
+    let complete = [&reference, &media].iter()
+        .all(|content| content.as_ref().map(|content| content.status != VerificationStatus::FetchFailed).unwrap_or(true));
+
+    TokenResolution {
+        reference,
+        media,
+        attempts: token.resolution.as_ref().map(|resolution| resolution.attempts).unwrap_or(0) + 1,
+        complete,
+    }
+}
+
+
+async fn resolve_content(
+    client: &reqwest::Client,
+    ipfs_gateway: &str,
+    base_uri: &Option<String>,
+    location: &str,
+    declared_hash: &Option<Vec<u8>>,
+    is_json: bool,
+) -> ResolvedContent {
+
+    let url = match get_url(ipfs_gateway, base_uri, location) {
+        Some(url) => url,
+        None => return ResolvedContent {
+            url: None,
+            status: VerificationStatus::Unresolvable,
+            content_type: None,
+            content: None,
+            error: Some(format!("{} is relative and the contract has no base_uri", location)),
+        },
+    };
+
+    info!(
+        target: crate::INDEXER,
+        "Resolving {}",
+        &url,
+    );
+
+    let max_length = match is_json {
+        true => MAX_REFERENCE_LENGTH,
+        false => MAX_MEDIA_LENGTH,
+    };
+
+    let fetched = match fetch(client, &url, max_length, is_json).await {
+        Ok(fetched) => fetched,
+        Err(error) => return ResolvedContent {
+            url: Some(url),
+            status: match error.is::<ContentTooLarge>() {
+                true => VerificationStatus::TooLarge,
+                false => VerificationStatus::FetchFailed,
+            },
+            content_type: None,
+            content: None,
+            error: Some(error.to_string()),
+        },
+    };
+
+    let status = match declared_hash {
+        Some(declared_hash) if fetched.hash == *declared_hash => VerificationStatus::Verified,
+        Some(_) => VerificationStatus::HashMismatch,
+        None => VerificationStatus::NoHash,
+    };
+
+    let (content, error) = match &fetched.body {
+        Some(body) => match serde_json::from_slice(body) {
+            Ok(content) => (Some(content), None),
+            Err(error) => (None, Some(format!("Reference is not JSON: {}", error))),
+        },
+        None => (None, None),
+    };
+
+    ResolvedContent {
+        url: Some(url),
+        status,
+        content_type: fetched.content_type,
+        content,
+        error,
+    }
+}
+
+
+/// Hashes the content as it is received and gives up once it goes past `max_length`,
+/// so large media never sits in memory
+async fn fetch(
+    client: &reqwest::Client,
+    url: &str,
+    max_length: usize,
+    keep_body: bool,
+) -> anyhow::Result<FetchedContent> {
+
+    let mut response = client.get(url).send().await?.error_for_status()?;
+
+    if response.content_length().map(|content_length| content_length > max_length as u64).unwrap_or(false) {
+        return Err(ContentTooLarge { max_length }.into());
+    }
+
+    let content_type = response.headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|content_type| content_type.to_str().ok())
+        .map(|content_type| content_type.to_string());
+
+    let mut hasher = Sha256::new();
+    let mut body: Option<Vec<u8>> = if keep_body { Some(Vec::new()) } else { None };
+    let mut length = 0usize;
+
+    while let Some(chunk) = response.chunk().await? {
+        length += chunk.len();
+
+        if length > max_length {
+            return Err(ContentTooLarge { max_length }.into());
+        }
+
+        hasher.update(&chunk);
+
+        if let Some(body) = body.as_mut() {
+            body.extend_from_slice(&chunk);
+        }
+    }
+
+    Ok(FetchedContent {
+        content_type,
+        hash: hasher.finalize().to_vec(),
+        body,
+    })
+}
+
+
+/// Absolute HTTP URL of the content. `ipfs://` URLs go through the gateway and relative ones are joined to `base_uri`
+pub(crate) fn get_url(
+    ipfs_gateway: &str,
+    base_uri: &Option<String>,
+    location: &str,
+) -> Option<String> {
+
+    let location = location.trim();
+
+    if let Some(path) = location.strip_prefix("ipfs://") {
+        return Some(format!("{}/{}", ipfs_gateway.trim_end_matches('/'), path.trim_start_matches('/')));
+    }
+
+    if location.starts_with("http://") || location.starts_with("https://") {
+        return Some(location.to_string());
+    }
+
+    let base_uri = get_url(ipfs_gateway, &None, base_uri.as_ref()?)?;
+
+    Some(format!("{}/{}", base_uri.trim_end_matches('/'), location.trim_start_matches('/')))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{ Read, Write };
+    use std::net::TcpListener;
+
+    /// Serves a single response on a local port and returns its URL
+    fn serve_once(content_type: &str, body: Vec<u8>, announce_length: bool) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("Failed to bind stand-in server");
+        let url = format!("http://{}/content", listener.local_addr().unwrap());
+        let content_type = content_type.to_string();
+
+        std::thread::spawn(move || {
+            let (mut stream, _) = match listener.accept() {
+                Ok(connection) => connection,
+                Err(_) => return,
+            };
+
+            let mut request = Vec::new();
+            let mut buffer = [0u8; 1024];
+            while !request.ends_with(b"\r\n\r\n") {
+                match stream.read(&mut buffer) {
+                    Ok(0) | Err(_) => return,
+                    Ok(read) => request.extend_from_slice(&buffer[..read]),
+                }
+            }
+
+            let mut head = format!("HTTP/1.1 200 OK\r\nContent-Type: {}\r\nConnection: close\r\n", content_type);
+            if announce_length {
+                head.push_str(&format!("Content-Length: {}\r\n", body.len()));
+            }
+            head.push_str("\r\n");
+
+            // The client hangs up on content that is too large
+            let _ = stream.write_all(head.as_bytes()).and_then(|_| stream.write_all(&body));
+        });
+
+        url
+    }
+
+    fn client() -> reqwest::Client {
+        reqwest::Client::builder().timeout(FETCH_TIMEOUT).build().unwrap()
+    }
+
+    #[actix_web::test]
+    async fn reference_is_parsed_and_verified() {
+        let body = br#"{"attributes":[{"trait_type":"Hat","value":"Crown"}]}"#.to_vec();
+        let hash = Sha256::digest(&body).to_vec();
+        let url = serve_once("application/json", body, true);
+
+        let content = resolve_content(&client(), IPFS_GATEWAY, &None, &url, &Some(hash), true).await;
+
+        assert_eq!(content.status, VerificationStatus::Verified);
+        assert_eq!(content.content_type.as_deref(), Some("application/json"));
+        assert_eq!(content.content, Some(serde_json::json!({ "attributes": [{ "trait_type": "Hat", "value": "Crown" }] })));
+        assert!(content.error.is_none());
+    }
+
+    #[actix_web::test]
+    async fn media_hash_mismatch_is_reported() {
+        let url = serve_once("image/png", vec![1, 2, 3], true);
+
+        let content = resolve_content(&client(), IPFS_GATEWAY, &None, &url, &Some(vec![0u8; 32]), false).await;
+
+        assert_eq!(content.status, VerificationStatus::HashMismatch);
+        assert!(content.content.is_none());
+    }
+
+    #[actix_web::test]
+    async fn reference_that_is_not_json_keeps_its_status() {
+        let url = serve_once("text/plain", b"not json".to_vec(), true);
+
+        let content = resolve_content(&client(), IPFS_GATEWAY, &None, &url, &None, true).await;
+
+        assert_eq!(content.status, VerificationStatus::NoHash);
+        assert!(content.content.is_none());
+        assert!(content.error.is_some());
+    }
+
+    #[actix_web::test]
+    async fn announced_length_past_the_limit_is_refused() {
+        let url = serve_once("image/png", vec![0u8; 64], true);
+
+        let error = fetch(&client(), &url, 32, false).await.err().expect("Content should be too large");
+
+        assert!(error.is::<ContentTooLarge>());
+    }
+
+    #[actix_web::test]
+    async fn streamed_content_past_the_limit_is_aborted() {
+        let url = serve_once("image/png", vec![0u8; 64 * 1024], false);
+
+        let error = fetch(&client(), &url, 1024, false).await.err().expect("Content should be too large");
+
+        assert!(error.is::<ContentTooLarge>());
+    }
+
+    #[actix_web::test]
+    async fn media_body_is_hashed_but_not_kept() {
+        let body = vec![7u8; 4096];
+        let url = serve_once("image/png", body.clone(), false);
+
+        let fetched = fetch(&client(), &url, MAX_MEDIA_LENGTH, false).await.unwrap();
+
+        assert_eq!(fetched.hash, Sha256::digest(&body).to_vec());
+        assert!(fetched.body.is_none());
+    }
+
+    #[test]
+    fn urls_are_resolved_through_the_gateway_and_base_uri() {
+        let base_uri = Some("ipfs://bafybase/".to_string());
+
+        assert_eq!(get_url("https://gateway.test/ipfs/", &None, "ipfs://bafy/1.json"), Some("https://gateway.test/ipfs/bafy/1.json".to_string()));
+        assert_eq!(get_url("https://gateway.test/ipfs/", &base_uri, "/1.json"), Some("https://gateway.test/ipfs/bafybase/1.json".to_string()));
+        assert_eq!(get_url("https://gateway.test/ipfs/", &None, "1.json"), None);
+    }
+}
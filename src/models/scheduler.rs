@@ -0,0 +1,210 @@
+use near_indexer::near_primitives::views::BlockHeaderView;
+use tokio::sync::watch;
+use tracing::{ info, warn };
+
+use crate::db_adapters;
+use crate::db_adapters::tokens::{ NotifyRetry, OwnershipEvent, OwnershipHistoryEntry, OwnershipHistoryRecord, TokenDB, TokenTimeField };
+use crate::gg_adapters;
+
+// Tokens handled per boundary and block, the rest are picked up by the next blocks
+const SCHEDULER_BATCH_SIZE: i64 = 100;
+// Blocks before a rejected notification is sent again, doubled with every attempt up to the max
+const NOTIFY_RETRY_BLOCKS: u64 = 10;
+const NOTIFY_RETRY_MAX_BLOCKS: u64 = 3_600;
+
+
+/// Processes the latest block header passed by `listen_blocks`. Headers passed meanwhile are skipped,
+/// chain time only moves forward so the next one covers their tokens
+pub(crate) async fn run_scheduler(
+    mut block_headers: watch::Receiver<Option<BlockHeaderView>>,
+) {
+
+    let pool = crate::models::get_mongo_client().await;
+
+    while block_headers.changed().await.is_ok() {
+        let block_header = block_headers.borrow().clone();
+
+        if let Some(block_header) = block_header {
+            process_block_time(&pool, &block_header).await;
+        }
+    }
+}
+
+/// Emits `token_expired` and `token_activated` once the block time crosses `expires_at` and `starts_at`.
+/// Chain time is used instead of wall time, so replayed blocks produce the same events
+async fn process_block_time(
+    pool: &mongodb::Client,
+    block_header: &BlockHeaderView,
+) {
+
+    for field in [TokenTimeField::StartsAt, TokenTimeField::ExpiresAt] {
+        match db_adapters::tokens::get_tokens_due(&pool, field, block_header.timestamp_nanosec / 1_000_000, block_header.height, SCHEDULER_BATCH_SIZE).await {
+            Ok(tokens) => {
+                for token in tokens {
+                    process_due_token(pool, block_header, field, token).await;
+                }
+            },
+            Err(error) => warn!( target: crate::INDEXER, "Error reading due tokens from database: {:?}", &error ),
+        }
+    }
+}
+
+async fn process_due_token(
+    pool: &mongodb::Client,
+    block_header: &BlockHeaderView,
+    field: TokenTimeField,
+    token: TokenDB,
+) {
+
+    let metadata = token.metadata.clone().unwrap_or_default();
+    let boundary_ms = match field {
+        TokenTimeField::ExpiresAt => metadata.expires_at,
+        TokenTimeField::StartsAt => metadata.starts_at,
+    };
+
+    let minted_past_boundary = is_minted_past_boundary(get_mint_timestamp_ms(&token), boundary_ms);
+
+    let event = match field {
+        TokenTimeField::ExpiresAt => OwnershipEvent::TokenExpired,
+        TokenTimeField::StartsAt => OwnershipEvent::TokenActivated,
+    };
+
+    let history_entry = match minted_past_boundary {
+        true => None,
+        false => Some(OwnershipHistoryEntry {
+            event: event.clone(),
+            owner: token.current_owner.clone(),
+            previous_owner: token.current_owner.clone(),
+            block_height: Some(block_header.height),
+            block_timestamp: Some(block_header.timestamp_nanosec),
+            receipt_id: None,
+            authorized_id: None,
+            memo: None,
+        }),
+    };
+
+    // Marked only once the backend accepted the event, otherwise the token is due again after a backoff
+    if history_entry.is_some() {
+        info!(
+            target: crate::INDEXER,
+            "Token {} of contract {}: {:?} at height {}",
+            &token.token_id, &token.contract_id, &event, &block_header.height,
+        );
+
+        let result = match event {
+            OwnershipEvent::TokenExpired => gg_adapters::expire_game_asset(token.contract_id.clone(), token.token_id.clone(), token.current_owner.clone()).await,
+            _ => gg_adapters::activate_game_asset(token.contract_id.clone(), token.token_id.clone(), token.current_owner.clone()).await,
+        };
+
+        match result {
+            Err(error) => {
+                warn!( target: crate::INDEXER, "Error! Coudn't notify server: {:?}", &error);
+
+                let retry = get_notify_retry(token.notify_retry(field), block_header.height);
+                match db_adapters::tokens::record_notify_retry(&pool, token.id().to_string(), field, retry).await {
+                    Err(error) => warn!( target: crate::INDEXER, "Error adding notification retry to database: {:?}", &error ),
+                    _ => (),
+                }
+                return;
+            },
+            _ => (),
+        }
+    }
+
+    match db_adapters::tokens::mark_token_due(&pool, token.id().to_string(), field, block_header.height, history_entry).await {
+        Err(error) => warn!( target: crate::INDEXER, "Error adding token time event to database: {:?}", &error ),
+        _ => (),
+    }
+}
+
+/// Tokens minted past the boundary never crossed it. Without a known mint time the boundary counts as crossed
+fn is_minted_past_boundary(
+    mint_timestamp_ms: Option<u64>,
+    boundary_ms: Option<u64>,
+) -> bool {
+    match (mint_timestamp_ms, boundary_ms) {
+        (Some(mint_timestamp_ms), Some(boundary_ms)) => mint_timestamp_ms >= boundary_ms,
+        _ => false,
+    }
+}
+
+/// Block timestamps are in nanoseconds, metadata times in milliseconds
+fn get_mint_timestamp_ms(token: &TokenDB) -> Option<u64> {
+    token.ownership_history.iter()
+        .find_map(|record| match record {
+            OwnershipHistoryRecord::Entry(entry) if entry.event == OwnershipEvent::Mint => entry.block_timestamp,
+            _ => None,
+        })
+        .map(|block_timestamp| block_timestamp / 1_000_000)
+}
+
+fn get_notify_retry(
+    previous_retry: Option<&NotifyRetry>,
+    block_height: u64,
+) -> NotifyRetry {
+    let attempts = previous_retry.map(|retry| retry.attempts).unwrap_or(0) + 1;
+    let delay = NOTIFY_RETRY_BLOCKS
+        .saturating_mul(1 << (attempts - 1).min(16))
+        .min(NOTIFY_RETRY_MAX_BLOCKS);
+
+    NotifyRetry {
+        attempts,
+        retry_at_height: block_height + delay,
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn tokens_minted_past_the_boundary_get_no_event() {
+        assert!(is_minted_past_boundary(Some(2_000), Some(1_000)));
+        assert!(is_minted_past_boundary(Some(1_000), Some(1_000)));
+        assert!(!is_minted_past_boundary(Some(999), Some(1_000)));
+        assert!(!is_minted_past_boundary(None, Some(1_000)));
+        assert!(!is_minted_past_boundary(Some(2_000), None));
+    }
+
+    #[test]
+    fn mint_timestamps_are_converted_to_milliseconds() {
+        let token: TokenDB = serde_json::from_value(json!({
+            "_id": "v1:game.near:1",
+            "contract_id": "game.near",
+            "token_id": "1",
+            "ownership_history": [
+                "alice.near",
+                { "event": "transfer", "owner": "bob.near", "block_timestamp": 1_700_000_500_000_000_000u64 },
+                { "event": "mint", "owner": "alice.near", "block_timestamp": 1_700_000_000_123_456_789u64 },
+            ],
+        })).unwrap();
+
+        assert_eq!(get_mint_timestamp_ms(&token), Some(1_700_000_000_123));
+    }
+
+    #[test]
+    fn tokens_without_an_indexed_mint_have_no_mint_timestamp() {
+        let token: TokenDB = serde_json::from_value(json!({
+            "_id": "v1:game.near:1",
+            "contract_id": "game.near",
+            "token_id": "1",
+            "ownership_history": ["alice.near"],
+        })).unwrap();
+
+        assert_eq!(get_mint_timestamp_ms(&token), None);
+    }
+
+    #[test]
+    fn rejected_notifications_back_off() {
+        let retry = get_notify_retry(None, 100);
+        assert_eq!(retry, NotifyRetry { attempts: 1, retry_at_height: 110 });
+
+        let retry = get_notify_retry(Some(&retry), 110);
+        assert_eq!(retry, NotifyRetry { attempts: 2, retry_at_height: 130 });
+
+        let retry = get_notify_retry(Some(&NotifyRetry { attempts: 40, retry_at_height: 0 }), 1_000);
+        assert_eq!(retry, NotifyRetry { attempts: 41, retry_at_height: 1_000 + NOTIFY_RETRY_MAX_BLOCKS });
+    }
+}
@@ -0,0 +1,77 @@
+use near_indexer::near_primitives::types::AccountId;
+use serde::Serialize;
+
+use crate::db_adapters;
+
+
+/// Holders of a contract's tokens at a block height, built from the indexed ownership history only
+#[derive(Debug, Serialize)]
+pub(crate) struct Snapshot {
+    pub contract_id: AccountId,
+    pub collection_name: Option<String>,
+    pub block_height: u64,
+    /// Largest holders first
+    pub holders: Vec<SnapshotHolder>,
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct SnapshotHolder {
+    pub owner: String,
+    pub count: usize,
+    pub token_ids: Vec<String>,
+}
+
+impl Snapshot {
+    /// `owner,count,token_ids` with token ids separated by spaces
+    pub(crate) fn to_csv(&self) -> String {
+        let mut csv = String::from("owner,count,token_ids\n");
+
+        for holder in &self.holders {
+            csv.push_str(&format!(
+                "{},{},{}\n",
+                escape_csv_field(&holder.owner),
+                holder.count,
+                escape_csv_field(&holder.token_ids.join(" ")),
+            ));
+        }
+
+        csv
+    }
+}
+
+/// Token ids are free-form, fields with separators or quotes are quoted
+fn escape_csv_field(field: &str) -> String {
+    if field.contains(|character| matches!(character, ',' | '"' | '\n' | '\r')) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+
+pub(crate) async fn take_snapshot(
+    pool: &mongodb::Client,
+    contract_id: AccountId,
+    collection_name: Option<String>,
+    block_height: u64,
+) -> anyhow::Result<Snapshot> {
+
+    let holders = db_adapters::tokens::get_holders_at_height(pool, contract_id.clone(), collection_name.clone(), block_height).await?;
+
+    let mut holders: Vec<SnapshotHolder> = holders.into_iter()
+        .map(|(owner, mut token_ids)| {
+            token_ids.sort();
+            SnapshotHolder { owner, count: token_ids.len(), token_ids }
+        })
+        .collect();
+
+    // Stable sort keeps owners with the same count in alphabetical order
+    holders.sort_by(|holder, other| other.count.cmp(&holder.count));
+
+    Ok(Snapshot {
+        contract_id,
+        collection_name,
+        block_height,
+        holders,
+    })
+}
@@ -0,0 +1,116 @@
+use serde_json::value::Value;
+use tracing::{ info, warn };
+use mongodb::bson::doc;
+
+use crate::events::{ Nep393EventKind, SbtMintData };
+use crate::db_adapters;
+use crate::db_adapters::soulbound_tokens::SoulboundTokenDB;
+use crate::gg_adapters;
+use crate::models::receipt_context::ReceiptContext;
+
+
+pub(crate) async fn process_soulbound_event(
+    pool: &mongodb::Client,
+    context: &ReceiptContext,
+    event_kind: &Nep393EventKind,
+) {
+
+    let registry_id = context.contract_id.clone();
+
+    let result = match event_kind {
+        Nep393EventKind::Mint(mint) => process_soulbound_mint(pool, context, mint).await,
+        Nep393EventKind::Recover(recover) => {
+            let update = doc!{ "$set": { "owner": recover.new_owner.clone() } };
+            db_adapters::soulbound_tokens::update_soulbound_tokens(&pool, registry_id, recover.ctr.clone(), &recover.tokens, update).await
+        },
+        Nep393EventKind::Renew(renew) => {
+            // The new expiry is only known from the `sbt_renew` args
+            match context.find_function_call_args("sbt_renew").and_then(|args| args.get("expires_at")).and_then(|expires_at| expires_at.as_u64()) {
+                Some(expires_at) => {
+                    let update = doc!{ "$set": { "expires_at": expires_at as i64 } };
+                    db_adapters::soulbound_tokens::update_soulbound_tokens(&pool, registry_id, renew.ctr.clone(), &renew.tokens, update).await
+                },
+                None => Err(anyhow::anyhow!("Renewed expiry of soulbound tokens {:?} is unknown", &renew.tokens)),
+            }
+        },
+        Nep393EventKind::Revoke(revoke) => {
+            let burn = context.find_function_call_args("sbt_revoke")
+                .and_then(|args| args.get("burn"))
+                .and_then(|burn| burn.as_bool())
+                .unwrap_or(false);
+
+            let update = doc!{ "$set": { "revoked": true, "burned": burn } };
+
+            match db_adapters::soulbound_tokens::update_soulbound_tokens(&pool, registry_id.clone(), revoke.ctr.clone(), &revoke.tokens, update).await {
+                Ok(_) => gg_adapters::revoke_soulbound_tokens(registry_id, revoke.ctr.clone(), revoke.tokens.clone()).await,
+                Err(error) => Err(error),
+            }
+        },
+        Nep393EventKind::Burn(burn) => {
+            let update = doc!{ "$set": { "burned": true } };
+            db_adapters::soulbound_tokens::update_soulbound_tokens(&pool, registry_id, burn.ctr.clone(), &burn.tokens, update).await
+        },
+        Nep393EventKind::Ban(accounts) => db_adapters::soulbound_tokens::ban_accounts(&pool, registry_id, accounts).await,
+        Nep393EventKind::SoulTransfer(soul_transfer) => {
+            db_adapters::soulbound_tokens::transfer_soul(&pool, registry_id, soul_transfer.from.clone(), soul_transfer.to.clone()).await
+        },
+    };
+
+    match result {
+        Err(error) => warn!( target: crate::INDEXER, "Error processing soulbound token event: {:?}", &error ),
+        _ => (),
+    }
+}
+
+
+async fn process_soulbound_mint(
+    pool: &mongodb::Client,
+    context: &ReceiptContext,
+    mint: &SbtMintData,
+) -> anyhow::Result<()> {
+
+    // `sbt_mint(token_spec)` lists the metadata for each owner in the same order the token ids are logged
+    let token_metadata = get_token_spec_metadata(context.find_function_call_args("sbt_mint"));
+    let mut token_metadata_iter = token_metadata.iter();
+
+    for (owner, token_ids) in &mint.tokens {
+        for token_id in token_ids {
+
+            let mut token = SoulboundTokenDB::new(context.contract_id.clone(), mint.ctr.clone(), *token_id, owner.clone());
+
+            if let Some(metadata) = token_metadata_iter.next() {
+                token.class = metadata.get("class").and_then(|class| class.as_u64());
+                token.issued_at = metadata.get("issued_at").and_then(|issued_at| issued_at.as_u64());
+                token.expires_at = metadata.get("expires_at").and_then(|expires_at| expires_at.as_u64());
+                token.reference = metadata.get("reference").and_then(|reference| reference.as_str()).map(|reference| reference.to_string());
+            }
+
+            info!(
+                target: crate::INDEXER,
+                "Minted soulbound token: {:#?}",
+                &token,
+            );
+
+            db_adapters::soulbound_tokens::store_soulbound_token(&pool, token.clone()).await?;
+
+            match gg_adapters::mint_soulbound_token(token.registry_id, token.issuer, token.token_id, token.owner, token.class).await {
+                Err(error) => warn!( target: crate::INDEXER, "Error! Coudn't notify server: {:?}", &error),
+                _ => (),
+            }
+        }
+    }
+
+    Ok(())
+}
+
+
+/// Flattens `token_spec: [[owner, [metadata, ...]], ...]`
+fn get_token_spec_metadata(args: Option<&Value>) -> Vec<&Value> {
+    args.and_then(|args| args.get("token_spec"))
+        .and_then(|token_spec| token_spec.as_array())
+        .map(|token_spec| token_spec.iter()
+            .filter_map(|owner_spec| owner_spec.get(1).and_then(|metadata| metadata.as_array()))
+            .flatten()
+            .collect())
+        .unwrap_or_default()
+}
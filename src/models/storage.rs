@@ -0,0 +1,137 @@
+use serde_json::value::Value;
+use tracing::{ info, warn };
+
+use crate::functions;
+use crate::db_adapters;
+use crate::db_adapters::storage_registrations::StorageRegistrationDB;
+use crate::models::receipt_context::ReceiptContext;
+
+
+/// Balance change of a single NEP-145 call
+struct StorageChange {
+    account_id: String,
+    registered: bool,
+    total: u128,
+    available: u128,
+    deposit: u128,
+}
+
+
+/// Indexes NEP-145 `storage_deposit`, `storage_withdraw` and `storage_unregister` calls of the receipt
+pub(crate) async fn process_storage_calls(
+    pool: &mongodb::Client,
+    context: &ReceiptContext,
+) {
+
+    for (index, function_call) in context.function_calls.iter().enumerate() {
+
+        let method_name = match functions::get_method_name(function_call) {
+            Some(method_name) => method_name,
+            None => continue,
+        };
+
+        // All NEP-145 args are optional, calls without args have no `args_json`
+        let args = functions::get_args_json(function_call).cloned().unwrap_or(Value::Null);
+        let predecessor_id = context.predecessor_id.to_string();
+        let success_value = context.get_success_value(index);
+
+        let change = match method_name {
+            "storage_deposit" => get_storage_balance(success_value).map(|(total, available)| StorageChange {
+                account_id: args.get("account_id").and_then(|account_id| account_id.as_str()).map(|account_id| account_id.to_string()).unwrap_or(predecessor_id),
+                registered: true,
+                total,
+                available,
+                deposit: functions::get_deposit(function_call),
+            }),
+            "storage_withdraw" => get_storage_balance(success_value).map(|(total, available)| StorageChange {
+                account_id: predecessor_id,
+                registered: true,
+                total,
+                available,
+                deposit: 0,
+            }),
+            // Returns `false` when the account wasn't registered, nothing changes then
+            "storage_unregister" => match success_value {
+                Some(Value::Bool(true)) => Some(StorageChange {
+                    account_id: predecessor_id,
+                    registered: false,
+                    total: 0,
+                    available: 0,
+                    deposit: 0,
+                }),
+                _ => None,
+            },
+            _ => continue,
+        };
+
+        let change = match change {
+            Some(change) => change,
+            None => {
+                warn!(
+                    target: crate::INDEXER,
+                    "Unexpected {} result: {:#?}",
+                    &method_name, &success_value,
+                );
+                continue;
+            },
+        };
+
+        match update_storage_registration(&pool, context, change).await {
+            Err(error) => warn!( target: crate::INDEXER, "Error updating storage registration in database: {:?}", &error ),
+            _ => (),
+        }
+    }
+}
+
+
+async fn update_storage_registration(
+    pool: &mongodb::Client,
+    context: &ReceiptContext,
+    change: StorageChange,
+) -> anyhow::Result<()> {
+
+    let previous = db_adapters::storage_registrations::get_storage_registration(&pool, context.contract_id.clone(), change.account_id.clone()).await?;
+
+    let (previous_total, deposited, refunded) = match &previous {
+        Some(previous) => (parse_balance(&previous.total), parse_balance(&previous.deposited), parse_balance(&previous.refunded)),
+        None => (0, 0, 0),
+    };
+
+    // Whatever was attached and didn't end up in the storage balance has been refunded,
+    // and whatever left the storage balance was sent back to the account
+    let refund = (previous_total + change.deposit).saturating_sub(change.total);
+
+    info!(
+        target: crate::INDEXER,
+        "Storage balance of {} on {}: {} -> {}, refund {}",
+        &change.account_id, &context.contract_id, &previous_total, &change.total, &refund,
+    );
+
+    let registration = StorageRegistrationDB {
+        contract_id: context.contract_id.clone(),
+        account_id: change.account_id,
+        registered: change.registered,
+        total: change.total.to_string(),
+        available: change.available.to_string(),
+        deposited: (deposited + change.deposit).to_string(),
+        refunded: (refunded + refund).to_string(),
+        last_receipt_id: context.receipt_id.clone(),
+    };
+
+    db_adapters::storage_registrations::store_storage_registration(&pool, registration).await
+}
+
+
+fn parse_balance(balance: &str) -> u128 {
+    balance.parse::<u128>().unwrap_or(0)
+}
+
+
+/// `StorageBalance` is returned as `{ "total": "<U128>", "available": "<U128>" }`
+fn get_storage_balance(success_value: Option<&Value>) -> Option<(u128, u128)> {
+    let balance = success_value?;
+    let total = balance.get("total")?.as_str()?.parse::<u128>().ok()?;
+    let available = balance.get("available")?.as_str()?.parse::<u128>().ok()?;
+
+    Some((total, available))
+}
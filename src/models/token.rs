@@ -3,10 +3,16 @@ use tracing::{ info, warn };
 use near_indexer::near_primitives::types::AccountId;
 use serde::{Deserialize, Serialize};
 use futures::try_join;
+use std::collections::{ HashMap, VecDeque };
 
 use crate::events::{ Nep171EventKind, Nep141EventKind, NearEvent, NftMintData, NftTransferData, NftBurnData, FtTransferData };
+use crate::configs::WatchConfig;
 use crate::db_adapters;
+use crate::db_adapters::tokens::{ MintedBy, OwnershipEvent, OwnershipHistoryEntry, TokenDB };
+use crate::db_adapters::anomalies::{ AnomalyDB, AnomalyKind };
 use crate::gg_adapters;
+use crate::models::{ anomaly, collection, contract, custom_event, inventory, leaderboard, rarity, soulbound };
+use crate::models::receipt_context::ReceiptContext;
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub(crate) struct Token {
@@ -15,62 +21,266 @@ pub(crate) struct Token {
     pub metadata: Option<TokenMetadata>,
 }
 
-// media_hash & reference_hash must be Base64VecU8 instead of String
-// copies must be U64
-
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
 pub struct TokenMetadata {
     pub title: Option<String>, // ex. "Arch Nemesis: Mail Carrier" or "Parcel #5055"
     pub description: Option<String>, // free-form description
     pub media: Option<String>, // URL to associated media, preferably to decentralized, content-addressed storage
-    pub media_hash: Option<String>, // Base64-encoded sha256 hash of content referenced by the `media` field. Required if `media` is included.
-    pub copies: Option<String>, // number of copies of this set of metadata in existence when token was minted.
+    pub media_hash: Option<Vec<u8>>, // sha256 hash of content referenced by the `media` field, decoded from base64
+    pub copies: Option<u64>, // number of copies of this set of metadata in existence when token was minted.
     pub rarity : Option <String>,
     pub nft_type : Option<String>,
     pub collection_name : Option<String>,
-    pub issued_at: Option<String>, // ISO 8601 datetime when token was issued or minted
-    pub expires_at: Option<String>, // ISO 8601 datetime when token expires
-    pub starts_at: Option<String>, // ISO 8601 datetime when token starts being valid
-    pub updated_at: Option<String>, // ISO 8601 datetime when token was last updated
-    pub extra: Option<String>, // anything extra the NFT wants to store on-chain. Can be stringified JSON.
+    pub issued_at: Option<u64>, // epoch ms when token was issued or minted
+    pub expires_at: Option<u64>, // epoch ms when token expires
+    pub starts_at: Option<u64>, // epoch ms when token starts being valid
+    pub updated_at: Option<u64>, // epoch ms when token was last updated
+    pub extra: Option<Value>, // anything extra the NFT wants to store on-chain, decoded as JSON when possible
     pub reference: Option<String>, // URL to an off-chain JSON file with more info.
-    pub reference_hash: Option<String>, // Base64-encoded sha256 hash of JSON from reference field. Required if `reference` is included.
+    pub reference_hash: Option<Vec<u8>>, // sha256 hash of JSON from reference field, decoded from base64
     pub game_id: Option<String>,                //the game id
+    #[serde(default)]
+    pub unknown_fields: serde_json::Map<String, Value>, // fields outside of the standard, kept as they are
+    #[serde(default)]
+    pub parse_errors: Vec<MetadataFieldError>, // fields that couldn't be parsed, left empty above
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct MetadataFieldError {
+    pub field: String,
+    pub value: Value,
+    pub error: String,
 }
 
 
 impl TokenMetadata {
-    fn from_json(json: Option<&serde_json::value::Value>) -> Option<Self> {
-        if let Some(json) = json {
-            let metadata: TokenMetadata = serde_json::from_value(json.clone()).unwrap();
-            return Some(metadata);
+    /// Parses metadata field by field. A malformed field is reported in `parse_errors` and left empty,
+    /// it never fails the whole metadata
+    pub(crate) fn from_json(json: Option<&serde_json::value::Value>) -> Option<Self> {
+        let object_map = match json? {
+            Value::Object(object_map) => object_map,
+            Value::Null => return None,
+            other => {
+                warn!(
+                    target: crate::INDEXER,
+                    "Token metadata is not an object: {:#?}",
+                    &other,
+                );
+                return None;
+            },
+        };
+
+        let mut metadata = TokenMetadata::default();
+
+        for (field, value) in object_map {
+            if value.is_null() {
+                continue;
+            }
+
+            let result = match field.as_str() {
+                "title" => parse_string(value).map(|title| metadata.title = Some(title)),
+                "description" => parse_string(value).map(|description| metadata.description = Some(description)),
+                "media" => parse_string(value).map(|media| metadata.media = Some(media)),
+                "media_hash" => parse_hash(value).map(|media_hash| metadata.media_hash = Some(media_hash)),
+                "copies" => parse_u64(value).map(|copies| metadata.copies = Some(copies)),
+                "rarity" => parse_string(value).map(|rarity| metadata.rarity = Some(rarity)),
+                "nft_type" => parse_string(value).map(|nft_type| metadata.nft_type = Some(nft_type)),
+                "collection_name" => parse_string(value).map(|collection_name| metadata.collection_name = Some(collection_name)),
+                "issued_at" => parse_timestamp(value).map(|issued_at| metadata.issued_at = Some(issued_at)),
+                "expires_at" => parse_timestamp(value).map(|expires_at| metadata.expires_at = Some(expires_at)),
+                "starts_at" => parse_timestamp(value).map(|starts_at| metadata.starts_at = Some(starts_at)),
+                "updated_at" => parse_timestamp(value).map(|updated_at| metadata.updated_at = Some(updated_at)),
+                "extra" => {
+                    metadata.extra = Some(parse_extra(value));
+                    Ok(())
+                },
+                "reference" => parse_string(value).map(|reference| metadata.reference = Some(reference)),
+                "reference_hash" => parse_hash(value).map(|reference_hash| metadata.reference_hash = Some(reference_hash)),
+                "game_id" => parse_string(value).map(|game_id| metadata.game_id = Some(game_id)),
+                _ => {
+                    metadata.unknown_fields.insert(field.clone(), value.clone());
+                    Ok(())
+                },
+            };
+
+            if let Err(error) = result {
+                warn!(
+                    target: crate::INDEXER,
+                    "Token metadata field {} can't be parsed: {} \n{:#?}",
+                    &field, &error, &value,
+                );
+
+                metadata.parse_errors.push(MetadataFieldError {
+                    field: field.clone(),
+                    value: value.clone(),
+                    error,
+                });
+            }
+        }
+
+        Some(metadata)
+    }
+
+    /// Applies a partial update. Fields set in `update` replace the current ones,
+    /// `extra` and unknown fields objects are merged key by key
+    pub(crate) fn merge(&self, update: TokenMetadata) -> TokenMetadata {
+        // Earlier errors stand for the fields the update neither set nor failed to parse
+        let mut parse_errors: Vec<MetadataFieldError> = self.parse_errors.iter()
+            .filter(|parse_error| !update.has_field(&parse_error.field))
+            .filter(|parse_error| !update.parse_errors.iter().any(|update_error| update_error.field == parse_error.field))
+            .cloned()
+            .collect();
+        parse_errors.extend(update.parse_errors.iter().cloned());
+
+        let mut unknown_fields = self.unknown_fields.clone();
+        unknown_fields.extend(update.unknown_fields);
+
+        let extra = match (self.extra.clone(), update.extra) {
+            (Some(Value::Object(mut extra)), Some(Value::Object(extra_update))) => {
+                extra.extend(extra_update);
+                Some(Value::Object(extra))
+            },
+            (extra, extra_update) => extra_update.or(extra),
+        };
+
+        TokenMetadata {
+            title: update.title.or(self.title.clone()),
+            description: update.description.or(self.description.clone()),
+            media: update.media.or(self.media.clone()),
+            media_hash: update.media_hash.or(self.media_hash.clone()),
+            copies: update.copies.or(self.copies),
+            rarity: update.rarity.or(self.rarity.clone()),
+            nft_type: update.nft_type.or(self.nft_type.clone()),
+            collection_name: update.collection_name.or(self.collection_name.clone()),
+            issued_at: update.issued_at.or(self.issued_at),
+            expires_at: update.expires_at.or(self.expires_at),
+            starts_at: update.starts_at.or(self.starts_at),
+            updated_at: update.updated_at.or(self.updated_at),
+            extra,
+            reference: update.reference.or(self.reference.clone()),
+            reference_hash: update.reference_hash.or(self.reference_hash.clone()),
+            game_id: update.game_id.or(self.game_id.clone()),
+            unknown_fields,
+            parse_errors,
+        }
+    }
+
+    /// Whether the named field has a value
+    fn has_field(&self, field: &str) -> bool {
+        match field {
+            "title" => self.title.is_some(),
+            "description" => self.description.is_some(),
+            "media" => self.media.is_some(),
+            "media_hash" => self.media_hash.is_some(),
+            "copies" => self.copies.is_some(),
+            "rarity" => self.rarity.is_some(),
+            "nft_type" => self.nft_type.is_some(),
+            "collection_name" => self.collection_name.is_some(),
+            "issued_at" => self.issued_at.is_some(),
+            "expires_at" => self.expires_at.is_some(),
+            "starts_at" => self.starts_at.is_some(),
+            "updated_at" => self.updated_at.is_some(),
+            "extra" => self.extra.is_some(),
+            "reference" => self.reference.is_some(),
+            "reference_hash" => self.reference_hash.is_some(),
+            "game_id" => self.game_id.is_some(),
+            _ => self.unknown_fields.contains_key(field),
         }
-        None
+    }
+}
+
+
+fn parse_string(value: &Value) -> Result<String, String> {
+    match value {
+        Value::String(string) => Ok(string.clone()),
+        // Game ids and rarities are sometimes passed as numbers
+        Value::Number(number) => Ok(number.to_string()),
+        _ => Err("expected a string".to_string()),
+    }
+}
+
+
+fn parse_u64(value: &Value) -> Result<u64, String> {
+    match value {
+        Value::Number(number) => number.as_u64().ok_or("expected an unsigned integer".to_string()),
+        // U64 is serialized as a string
+        Value::String(string) => string.trim().parse::<u64>().map_err(|error| error.to_string()),
+        _ => Err("expected an unsigned integer".to_string()),
+    }
+}
+
+
+/// Base64VecU8 holding a sha256 hash
+fn parse_hash(value: &Value) -> Result<Vec<u8>, String> {
+    let hash = base64::decode(parse_string(value)?).map_err(|error| error.to_string())?;
+
+    match hash.len() {
+        32 => Ok(hash),
+        len => Err(format!("expected a 32 bytes sha256 hash, got {} bytes", len)),
+    }
+}
+
+
+/// ISO 8601 datetime or epoch milliseconds, as a number or a string
+fn parse_timestamp(value: &Value) -> Result<u64, String> {
+    if let Ok(timestamp) = parse_u64(value) {
+        return Ok(timestamp);
+    }
+
+    let datetime = parse_string(value)?;
+    let datetime = datetime.trim();
+
+    if let Ok(datetime) = chrono::DateTime::parse_from_rfc3339(datetime) {
+        return u64::try_from(datetime.timestamp_millis()).map_err(|error| error.to_string());
+    }
+
+    // Datetimes without a timezone are taken as UTC
+    let naive_datetime = chrono::NaiveDateTime::parse_from_str(datetime, "%Y-%m-%dT%H:%M:%S%.f").ok()
+        .or_else(|| chrono::NaiveDateTime::parse_from_str(datetime, "%Y-%m-%d %H:%M:%S%.f").ok())
+        .or_else(|| chrono::NaiveDate::parse_from_str(datetime, "%Y-%m-%d").ok().and_then(|date| date.and_hms_opt(0, 0, 0)))
+        .ok_or(format!("expected an ISO 8601 datetime or epoch milliseconds, got {}", datetime))?;
+
+    u64::try_from(naive_datetime.timestamp_millis()).map_err(|error| error.to_string())
+}
+
+
+/// `extra` is free-form, usually stringified JSON
+fn parse_extra(value: &Value) -> Value {
+    match value {
+        Value::String(string) => serde_json::from_str(string).unwrap_or(value.clone()),
+        _ => value.clone(),
     }
 }
 
 
 #[derive(Debug)]
 pub struct ArgsJsonTokenMint<'a> {
+    pub token_id: Option<&'a str>,
     pub token_metadata: Option<&'a serde_json::value::Value>,
 }
 
 
 pub(crate) async fn process_token_event(
     pool: &mongodb::Client,
-    contract_id: &AccountId,
-    receipt_args: &serde_json::Value,
+    config: &WatchConfig,
+    context: &ReceiptContext,
     events: &Vec<NearEvent>,
 ) {
 
+    // Mint args of every FunctionCall action are matched to minted tokens by `token_id`, or else
+    // in the order the mint events were logged
+    let token_args = get_token_args(&context.function_calls);
+    let mut mint_args = MintArgsMatcher::new(&token_args);
+
     for event in events {
         match event {
             NearEvent::Nep171(nep171event) => {
                 let event_kind = &nep171event.event_kind;
                 match event_kind {
-                    Nep171EventKind::NftMint(mints) => process_token_mint(pool, contract_id, receipt_args, mints).await,
-                    Nep171EventKind::NftTransfer(transfers) => process_token_transfer(pool, contract_id, transfers).await,
-                    Nep171EventKind::NftBurn(burns) => process_token_burn(pool, contract_id, burns).await,
+                    Nep171EventKind::NftMint(mints) => process_token_mint(pool, config, context, &mut mint_args, mints).await,
+                    Nep171EventKind::NftTransfer(transfers) => process_token_transfer(pool, context, transfers).await,
+                    Nep171EventKind::NftBurn(burns) => process_token_burn(pool, context, burns).await,
+                    Nep171EventKind::ContractMetadataUpdate(_) => contract::process_contract_metadata_update(pool, context).await,
                 }
             },
             NearEvent::Nep141(nep141event) => {
@@ -80,56 +290,67 @@ pub(crate) async fn process_token_event(
                     _ => (),
                 }
             },
+            NearEvent::Nep393(nep393event) => soulbound::process_soulbound_event(pool, context, &nep393event.event_kind).await,
+            NearEvent::Custom(custom_event) => custom_event::process_custom_event(pool, config, context, custom_event).await,
         }
         
     }
 }
 
 
-pub fn get_token_args(args: &serde_json::Value) -> Vec<ArgsJsonTokenMint> {
+pub fn get_token_args(function_calls: &[serde_json::Value]) -> Vec<ArgsJsonTokenMint> {
+
+    function_calls.iter().flat_map(get_function_call_token_args).collect()
+}
+
+
+fn get_function_call_token_args(args: &serde_json::Value) -> Vec<ArgsJsonTokenMint> {
 
     let mut tokens: Vec<ArgsJsonTokenMint> = Vec::new();
 
-    match args {
-        serde_json::Value::Object(object_map) => {
-
-            if let Some(data) = object_map.get("args_json") {
-                match data {
-                    serde_json::Value::Object(object_map) => {
-    
-                        info!(
-                            target: crate::INDEXER,
-                            "Object: {:#?}",
-                            &object_map,
-                        );
-
-                        if let Some(data) = object_map.get("tokens_to_mint") {
-                            match data {
-                                serde_json::Value::Array(vector) => {
-                                    for token_to_mint in vector {
-
-                                        let token_args = ArgsJsonTokenMint {
-                                            token_metadata: token_to_mint.get("metadata"),
-                                        };
-
-                                        info!(
-                                            target: crate::INDEXER,
-                                            "Unwrapped token args: {:#?}",
-                                            &token_args,
-                                        );
-                        
-                                        tokens.push(token_args);
-                                    }
-                                },
-                                _ => (),
-                            }
-                        }
-              
-                    },
-                    _ => (),
+    match args.get("args_json") {
+        Some(serde_json::Value::Object(object_map)) => {
+
+            info!(
+                target: crate::INDEXER,
+                "Object: {:#?}",
+                &object_map,
+            );
+
+            let metadata = object_map.get("metadata").or(object_map.get("token_metadata"));
+
+            if let Some(serde_json::Value::Array(vector)) = object_map.get("tokens_to_mint").or(object_map.get("tokens")) {
+                // Batch mints with an entry per token
+                for token_to_mint in vector {
+
+                    let token_args = ArgsJsonTokenMint {
+                        token_id: token_to_mint.get("token_id").and_then(|token_id| token_id.as_str()),
+                        token_metadata: token_to_mint.get("metadata").or(token_to_mint.get("token_metadata")),
+                    };
+
+                    info!(
+                        target: crate::INDEXER,
+                        "Unwrapped token args: {:#?}",
+                        &token_args,
+                    );
+
+                    tokens.push(token_args);
                 }
+            } else if let Some(serde_json::Value::Array(token_ids)) = object_map.get("token_ids") {
+                // Batch mints sharing the same metadata
+                for token_id in token_ids {
+                    tokens.push(ArgsJsonTokenMint {
+                        token_id: token_id.as_str(),
+                        token_metadata: metadata,
+                    });
+                }
+            } else if let Some(metadata) = metadata {
+                // Standard `nft_mint(token_id, metadata, receiver_id)`
+                tokens.push(ArgsJsonTokenMint {
+                    token_id: object_map.get("token_id").and_then(|token_id| token_id.as_str()),
+                    token_metadata: Some(metadata),
+                });
             }
-            
         },
         _ => (),
     }
@@ -138,14 +359,68 @@ pub fn get_token_args(args: &serde_json::Value) -> Vec<ArgsJsonTokenMint> {
 }
 
 
+/// Mint args of the receipt, matched to minted tokens by `token_id`.
+/// Args that don't name their token are matched in the order the tokens were logged
+pub(crate) struct MintArgsMatcher<'a> {
+    by_token_id: HashMap<&'a str, &'a ArgsJsonTokenMint<'a>>,
+    unnamed: VecDeque<&'a ArgsJsonTokenMint<'a>>,
+}
+
+impl<'a> MintArgsMatcher<'a> {
+    pub(crate) fn new(token_args: &'a [ArgsJsonTokenMint<'a>]) -> Self {
+        let mut by_token_id = HashMap::new();
+        let mut unnamed = VecDeque::new();
+
+        for args in token_args {
+            match args.token_id {
+                Some(token_id) => { by_token_id.insert(token_id, args); },
+                None => unnamed.push_back(args),
+            }
+        }
+
+        Self { by_token_id, unnamed }
+    }
+
+    pub(crate) fn take(&mut self, token_id: &str) -> Option<&'a ArgsJsonTokenMint<'a>> {
+        self.by_token_id.remove(token_id).or_else(|| self.unnamed.pop_front())
+    }
+
+    /// Whether any args are left, a token without args is then unexpected
+    pub(crate) fn has_args(&self) -> bool {
+        !self.by_token_id.is_empty() || !self.unnamed.is_empty()
+    }
+}
+
+
+fn get_history_entry(
+    context: &ReceiptContext,
+    event: OwnershipEvent,
+    owner: Option<String>,
+    previous_owner: Option<String>,
+    authorized_id: Option<String>,
+    memo: Option<String>,
+) -> OwnershipHistoryEntry {
+    OwnershipHistoryEntry {
+        event,
+        owner,
+        previous_owner,
+        block_height: Some(context.block_height),
+        block_timestamp: Some(context.block_timestamp),
+        receipt_id: Some(context.receipt_id.clone()),
+        authorized_id,
+        memo,
+    }
+}
+
+
 pub(super) async fn process_token_mint(
     pool: &mongodb::Client,
-    contract_id: &AccountId,
-    receipt_args: &serde_json::Value,
+    config: &WatchConfig,
+    context: &ReceiptContext,
+    mint_args: &mut MintArgsMatcher<'_>,
     mints: &Vec<NftMintData>,
 ) {
-
-    let token_args = get_token_args(receipt_args);
+    let contract_id = &context.contract_id;
 
     for mint in mints {
 
@@ -159,7 +434,7 @@ pub(super) async fn process_token_mint(
 
         let token_ids = &mint.token_ids;
 
-        for (i, token_id) in token_ids.iter().enumerate() {
+        for token_id in token_ids {
 
             info!(
                 target: crate::INDEXER,
@@ -167,10 +442,41 @@ pub(super) async fn process_token_mint(
                 &token_id,
             );
 
+            let had_args = mint_args.has_args();
+            let args = mint_args.take(token_id);
+
+            if args.is_none() && had_args {
+                anomaly::report_anomaly(pool, AnomalyDB {
+                    kind: AnomalyKind::MintArgsUnmatched,
+                    contract_id: contract_id.clone(),
+                    token_id: Some(token_id.clone()),
+                    receipt_id: context.receipt_id.clone(),
+                    block_height: context.block_height,
+                    block_timestamp: context.block_timestamp,
+                    expected: None,
+                    actual: Some(token_id.clone()),
+                    details: serde_json::to_value(mint).unwrap_or(Value::Null),
+                }).await;
+            }
+
+            if !config.is_allowed_minter(contract_id, &context.predecessor_id, &context.signer_id) {
+                anomaly::report_anomaly(pool, AnomalyDB {
+                    kind: AnomalyKind::UnexpectedMinter,
+                    contract_id: contract_id.clone(),
+                    token_id: Some(token_id.clone()),
+                    receipt_id: context.receipt_id.clone(),
+                    block_height: context.block_height,
+                    block_timestamp: context.block_timestamp,
+                    expected: None,
+                    actual: Some(context.predecessor_id.to_string()),
+                    details: serde_json::json!({ "signer_id": context.signer_id, "deposit": context.deposit.to_string() }),
+                }).await;
+            }
+
             info!(
                 target: crate::INDEXER,
                 "Token args: {:#?}",
-                &token_args,
+                &args,
             );
 
             let mut token = Token {
@@ -179,7 +485,7 @@ pub(super) async fn process_token_mint(
                 metadata: None
             };
 
-            if let Some(args) = token_args.get(i) {
+            if let Some(args) = args {
                 token.metadata = TokenMetadata::from_json(args.token_metadata)
             }
 
@@ -195,12 +501,25 @@ pub(super) async fn process_token_mint(
                 &token,
             );
 
-            match db_adapters::tokens::store_token(&pool, token.clone()).await {
+            let minted_by = MintedBy {
+                predecessor_id: context.predecessor_id.clone(),
+                signer_id: context.signer_id.clone(),
+                deposit: context.deposit.to_string(),
+            };
+
+            match db_adapters::tokens::store_token(&pool, token.clone(), context.block_height, context.receipt_id.clone(), minted_by).await {
                 Ok(_) => {
-                    match db_adapters::token_owners::add_token_owner(&pool, contract_id.clone(), token_id.clone(), owner_id.clone()).await {
+                    let history_entry = get_history_entry(context, OwnershipEvent::Mint, Some(owner_id.clone()), None, None, mint.memo.clone());
+
+                    match db_adapters::token_owners::add_token_owner(&pool, contract_id.clone(), token_id.clone(), owner_id.clone(), history_entry).await {
                         Err(error) => warn!( target: crate::INDEXER, "Error adding token owner to database: {:?}", &error ),
                         _ => (),
                     }
+
+                    collection::record_mint(pool, context, &token, owner_id).await;
+                    rarity::process_minted_token(pool, &token).await;
+                    inventory::record_mint(pool, contract_id, token_id, token.metadata.as_ref(), owner_id).await;
+                    leaderboard::record_mint(pool, context, token_id, owner_id).await;
                 },
                 Err(error) => warn!( target: crate::INDEXER, "Error adding token to database: {:?}", &error ),
             }
@@ -217,9 +536,11 @@ pub(super) async fn process_token_mint(
 
 pub(super) async fn process_token_transfer(
     pool: &mongodb::Client,
-    contract_id: &AccountId,
+    context: &ReceiptContext,
     transfers: &Vec<NftTransferData>,
 ) {
+    let contract_id = &context.contract_id;
+
     for transfer in transfers {
 
         let old_owner_id = &transfer.old_owner_id;
@@ -241,10 +562,50 @@ pub(super) async fn process_token_transfer(
                 &token_id,
             );
 
-            let add_new_owner_future = db_adapters::token_owners::add_token_owner(&pool, contract_id.clone(), token_id.clone(), new_owner_id.clone());
+            // Read once, the checks and aggregates below need the token as it was before the transfer
+            let (token, indexed_owner_id) = match db_adapters::tokens::get_token(&pool, contract_id.clone(), token_id.clone()).await {
+                Ok(token) => {
+                    let indexed_owner_id = validate_transfer(pool, context, transfer, token_id, token.as_ref()).await;
+                    (token, indexed_owner_id)
+                },
+                Err(error) => {
+                    warn!( target: crate::INDEXER, "Error reading token from database: {:?}", &error );
+                    (None, None)
+                },
+            };
+
+            // Tokens minted before indexing aren't in any aggregate
+            if let Some(token) = &token {
+                collection::record_transfer(pool, token, indexed_owner_id.as_ref().unwrap_or(old_owner_id), new_owner_id).await;
+                inventory::record_transfer(pool, token, indexed_owner_id.as_ref().unwrap_or(old_owner_id), new_owner_id).await;
+            }
+
+            leaderboard::record_transfer(pool, context, token.as_ref(), old_owner_id, new_owner_id).await;
+
+            if let Some(indexed_owner_id) = indexed_owner_id {
+                // The chain is the source of truth, the token can't stay in the wallet of the indexed owner
+                match db_adapters::token_owners::remove_token_owner(&pool, contract_id.clone(), token_id.clone(), indexed_owner_id).await {
+                    Err(error) => warn!( target: crate::INDEXER, "Error removing indexed owner from database: {:?}", &error ),
+                    _ => (),
+                }
+            }
+
+            let history_entry = get_history_entry(
+                context,
+                OwnershipEvent::Transfer,
+                Some(new_owner_id.clone()),
+                Some(old_owner_id.clone()),
+                transfer.authorized_id.clone(),
+                transfer.memo.clone(),
+            );
+
+            let add_new_owner_future = db_adapters::token_owners::add_token_owner(&pool, contract_id.clone(), token_id.clone(), new_owner_id.clone(), history_entry);
             let remove_old_owner_future = db_adapters::token_owners::remove_token_owner(&pool, contract_id.clone(), token_id.clone(), old_owner_id.clone());
 
-            match try_join!(add_new_owner_future, remove_old_owner_future) {
+            // Approvals don't survive a transfer
+            let clear_approvals_future = db_adapters::approvals::clear_approvals(&pool, contract_id.clone(), token_id.clone(), Some(new_owner_id.clone()));
+
+            match try_join!(add_new_owner_future, remove_old_owner_future, clear_approvals_future) {
                 Err(error) => {
                     warn!(
                         target: crate::INDEXER,
@@ -259,20 +620,122 @@ pub(super) async fn process_token_transfer(
 }
 
 
+/// Anomaly of a transfer from `old_owner_id` and the indexed owner of the token, if it disagrees
+fn get_transfer_anomaly(
+    token: Option<&TokenDB>,
+    old_owner_id: &str,
+) -> Option<(AnomalyKind, Option<String>)> {
+    match token {
+        None => Some((AnomalyKind::UnknownToken, None)),
+        Some(token) if token.burned => Some((AnomalyKind::TransferAfterBurn, None)),
+        Some(token) if token.current_owner.as_deref() != Some(old_owner_id) => Some((AnomalyKind::WrongOldOwner, token.current_owner.clone())),
+        Some(_) => None,
+    }
+}
+
+
+/// Checks the transfer against the indexed token and reports any mismatch as an anomaly.
+/// Returns the indexed owner when it isn't the old owner of the transfer
+async fn validate_transfer(
+    pool: &mongodb::Client,
+    context: &ReceiptContext,
+    transfer: &NftTransferData,
+    token_id: &str,
+    token: Option<&TokenDB>,
+) -> Option<String> {
+
+    let (kind, indexed_owner_id) = get_transfer_anomaly(token, &transfer.old_owner_id)?;
+
+    anomaly::report_anomaly(pool, AnomalyDB {
+        kind,
+        contract_id: context.contract_id.clone(),
+        token_id: Some(token_id.to_string()),
+        receipt_id: context.receipt_id.clone(),
+        block_height: context.block_height,
+        block_timestamp: context.block_timestamp,
+        expected: indexed_owner_id.clone(),
+        actual: Some(transfer.old_owner_id.clone()),
+        details: serde_json::to_value(transfer).unwrap_or(Value::Null),
+    }).await;
+
+    indexed_owner_id
+}
+
+
+/// Burned tokens have no owner, the burned owner is kept as `previous_owner`
+fn get_burn_history_entry(
+    context: &ReceiptContext,
+    burn: &NftBurnData,
+) -> OwnershipHistoryEntry {
+    get_history_entry(
+        context,
+        OwnershipEvent::Burn,
+        None,
+        Some(burn.owner_id.clone()),
+        burn.authorized_id.clone(),
+        burn.memo.clone(),
+    )
+}
+
+
 pub(super) async fn process_token_burn(
     pool: &mongodb::Client,
-    contract_id: &AccountId,
+    context: &ReceiptContext,
     burns: &Vec<NftBurnData>,
 ) {
+    let contract_id = &context.contract_id;
+
     for burn in burns {
 
+        let owner_id = &burn.owner_id;
         let token_ids = &burn.token_ids;
 
         info!(
             target: crate::INDEXER,
             "Burn token_ids: {:#?}",
             &token_ids,
-        )
+        );
+
+        for token_id in token_ids {
+
+            // Read once, the aggregates below need the token as it was before the burn
+            let token = match db_adapters::tokens::get_token(&pool, contract_id.clone(), token_id.clone()).await {
+                Ok(token) => token,
+                Err(error) => {
+                    warn!( target: crate::INDEXER, "Error reading token from database: {:?}", &error );
+                    None
+                },
+            };
+
+            if let Some(token) = &token {
+                collection::record_burn(pool, token, owner_id).await;
+                rarity::process_burned_token(pool, token).await;
+                inventory::record_burn(pool, token, owner_id).await;
+                leaderboard::record_burn(pool, token).await;
+            }
+
+            let history_entry = get_burn_history_entry(context, burn);
+
+            let burn_token_future = db_adapters::tokens::burn_token(&pool, contract_id.clone(), token_id.clone(), history_entry);
+            let remove_owner_future = db_adapters::token_owners::remove_token_owner(&pool, contract_id.clone(), token_id.clone(), owner_id.clone());
+            let clear_approvals_future = db_adapters::approvals::clear_approvals(&pool, contract_id.clone(), token_id.clone(), None);
+
+            match try_join!(burn_token_future, remove_owner_future, clear_approvals_future) {
+                Err(error) => {
+                    warn!(
+                        target: crate::INDEXER,
+                        "Error writing to database: {:?}",
+                        &error,
+                    )
+                },
+                _ => (),
+            }
+
+            match gg_adapters::burn_game_asset(contract_id.clone(), token_id.clone()).await {
+                Err(error) => warn!( target: crate::INDEXER, "Error! Coudn't notify server: {:?}", &error),
+                _ => (),
+            }
+        }
     }
 }
 
@@ -306,4 +769,317 @@ pub(super) async fn process_ft_transfer(
             _ => (),
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+
+    /// Pairing of mint args with logged tokens, see `MintArgsMatcher`
+    mod mint_args {
+        use super::super::*;
+        use serde_json::json;
+
+        fn function_call(args_json: Value) -> Value {
+            json!({ "method_name": "nft_mint", "args_json": args_json, "deposit": "0" })
+        }
+
+        fn get_title<'a>(args: Option<&'a ArgsJsonTokenMint<'a>>) -> Option<&'a str> {
+            args?.token_metadata?.get("title")?.as_str()
+        }
+
+        #[test]
+        fn mint_args_follow_token_ids_across_batched_calls() {
+            let function_calls = vec![
+                function_call(json!({ "token_id": "a", "metadata": { "title": "A" } })),
+                function_call(json!({ "token_id": "b", "metadata": { "title": "B" } })),
+            ];
+
+            let token_args = get_token_args(&function_calls);
+            let mut mint_args = MintArgsMatcher::new(&token_args);
+
+            // Logged in the opposite order of the calls
+            assert_eq!(get_title(mint_args.take("b")), Some("B"));
+            assert_eq!(get_title(mint_args.take("a")), Some("A"));
+            assert!(!mint_args.has_args());
+            assert!(mint_args.take("c").is_none());
+        }
+
+        #[test]
+        fn mint_args_without_token_ids_are_taken_in_order() {
+            let function_calls = vec![
+                function_call(json!({ "tokens_to_mint": [
+                    { "metadata": { "title": "First" } },
+                    { "metadata": { "title": "Second" } },
+                ] })),
+                function_call(json!({ "token_ids": ["x", "y"], "token_metadata": { "title": "Shared" } })),
+            ];
+
+            let token_args = get_token_args(&function_calls);
+            assert_eq!(token_args.len(), 4);
+
+            let mut mint_args = MintArgsMatcher::new(&token_args);
+
+            assert_eq!(get_title(mint_args.take("y")), Some("Shared"));
+            assert_eq!(get_title(mint_args.take("1")), Some("First"));
+            assert_eq!(get_title(mint_args.take("2")), Some("Second"));
+            assert_eq!(get_title(mint_args.take("x")), Some("Shared"));
+            assert!(!mint_args.has_args());
+        }
+
+        #[test]
+        fn calls_without_mint_args_are_ignored() {
+            let function_calls = vec![
+                function_call(json!({ "token_id": "a", "account_id": "market.near" })),
+                json!({ "method_name": "raw", "deposit": "0" }),
+            ];
+
+            let token_args = get_token_args(&function_calls);
+            let mint_args = MintArgsMatcher::new(&token_args);
+
+            assert!(!mint_args.has_args());
+        }
+
+        #[test]
+        fn tokens_with_unknown_ids_get_no_named_args() {
+            let function_calls = vec![
+                function_call(json!({ "token_id": "a", "metadata": { "title": "A" } })),
+            ];
+
+            let token_args = get_token_args(&function_calls);
+            let mut mint_args = MintArgsMatcher::new(&token_args);
+
+            // Args are left but none for this token, reported as `MintArgsUnmatched`
+            assert!(mint_args.take("b").is_none());
+            assert!(mint_args.has_args());
+            assert_eq!(get_title(mint_args.take("a")), Some("A"));
+        }
+    }
+
+    mod burn {
+        use super::super::*;
+
+        #[test]
+        fn burn_entries_keep_the_burned_owner_as_previous_owner() {
+            let context = ReceiptContext {
+                contract_id: "game.near".parse().unwrap(),
+                receipt_id: "receipt".to_string(),
+                block_height: 100,
+                block_timestamp: 1_700_000_000_000_000_000,
+                predecessor_id: "market.near".parse().unwrap(),
+                signer_id: "alice.near".parse().unwrap(),
+                deposit: 1,
+                function_calls: Vec::new(),
+                success_value: None,
+            };
+            let burn = NftBurnData {
+                authorized_id: Some("market.near".to_string()),
+                owner_id: "alice.near".to_string(),
+                token_ids: vec!["1".to_string()],
+                memo: Some("season over".to_string()),
+            };
+
+            let history_entry = get_burn_history_entry(&context, &burn);
+
+            assert_eq!(history_entry.event, OwnershipEvent::Burn);
+            assert_eq!(history_entry.owner, None);
+            assert_eq!(history_entry.previous_owner.as_deref(), Some("alice.near"));
+            assert_eq!(history_entry.authorized_id.as_deref(), Some("market.near"));
+            assert_eq!(history_entry.memo.as_deref(), Some("season over"));
+            assert_eq!(history_entry.block_height, Some(100));
+            assert_eq!(history_entry.block_timestamp, Some(1_700_000_000_000_000_000));
+            assert_eq!(history_entry.receipt_id.as_deref(), Some("receipt"));
+
+            let history_entry = serde_json::to_value(&history_entry).unwrap();
+            assert_eq!(history_entry["event"], "burn");
+            assert!(history_entry["owner"].is_null());
+        }
+    }
+
+    mod transfer {
+        use super::super::*;
+        use serde_json::json;
+
+        fn token(current_owner: Option<&str>, burned: bool) -> TokenDB {
+            serde_json::from_value(json!({
+                "_id": "v1:game.near:1",
+                "contract_id": "game.near",
+                "token_id": "1",
+                "current_owner": current_owner,
+                "burned": burned,
+            })).unwrap()
+        }
+
+        #[test]
+        fn transfers_from_the_indexed_owner_are_valid() {
+            assert!(get_transfer_anomaly(Some(&token(Some("alice.near"), false)), "alice.near").is_none());
+        }
+
+        #[test]
+        fn transfer_anomalies_are_told_apart() {
+            assert_eq!(get_transfer_anomaly(None, "alice.near"), Some((AnomalyKind::UnknownToken, None)));
+
+            // Burned first, the owner of a burned token is unset anyway
+            assert_eq!(get_transfer_anomaly(Some(&token(None, true)), "alice.near"), Some((AnomalyKind::TransferAfterBurn, None)));
+            assert_eq!(get_transfer_anomaly(Some(&token(Some("alice.near"), true)), "alice.near"), Some((AnomalyKind::TransferAfterBurn, None)));
+
+            assert_eq!(
+                get_transfer_anomaly(Some(&token(Some("bob.near"), false)), "alice.near"),
+                Some((AnomalyKind::WrongOldOwner, Some("bob.near".to_string()))),
+            );
+            assert_eq!(get_transfer_anomaly(Some(&token(None, false)), "alice.near"), Some((AnomalyKind::WrongOldOwner, None)));
+        }
+    }
+
+    /// Lenient parsing of `TokenMetadata`
+    mod metadata {
+        use super::super::*;
+        use serde_json::json;
+
+        // 2024-01-01T00:00:00Z
+        const NEW_YEAR_MS: u64 = 1_704_067_200_000;
+
+        #[test]
+        fn timestamps_accept_iso_8601_and_epoch_ms() {
+            let cases = [
+                (json!("2024-01-01T00:00:00Z"), NEW_YEAR_MS),
+                (json!("2024-01-01T02:00:00+02:00"), NEW_YEAR_MS),
+                (json!("2024-01-01T00:00:00.250Z"), NEW_YEAR_MS + 250),
+                // Without a timezone, taken as UTC
+                (json!("2024-01-01T00:00:00"), NEW_YEAR_MS),
+                (json!("2024-01-01 00:00:00.500"), NEW_YEAR_MS + 500),
+                (json!("2024-01-01"), NEW_YEAR_MS),
+                (json!(NEW_YEAR_MS), NEW_YEAR_MS),
+                (json!(NEW_YEAR_MS.to_string()), NEW_YEAR_MS),
+                (json!(" 1704067200000 "), NEW_YEAR_MS),
+            ];
+
+            for (value, expected) in cases {
+                assert_eq!(parse_timestamp(&value), Ok(expected), "{}", value);
+            }
+        }
+
+        #[test]
+        fn invalid_timestamps_are_errors() {
+            for value in [json!("yesterday"), json!(-5), json!(1.5), json!(true), json!({ "ms": 1 })] {
+                assert!(parse_timestamp(&value).is_err(), "{}", value);
+            }
+        }
+
+        #[test]
+        fn hashes_are_32_bytes_of_base64() {
+            let hash = vec![7u8; 32];
+            assert_eq!(parse_hash(&json!(base64::encode(&hash))), Ok(hash));
+
+            let error = parse_hash(&json!(base64::encode(vec![7u8; 16]))).unwrap_err();
+            assert!(error.contains("16 bytes"), "{}", error);
+
+            for value in [json!("not base64!"), json!(5), json!(null), json!([1, 2])] {
+                assert!(parse_hash(&value).is_err(), "{}", value);
+            }
+        }
+
+        #[test]
+        fn extra_is_decoded_as_json_when_possible() {
+            let cases = [
+                (json!(r#"{"attributes":[{"trait_type":"hat","value":"red"}]}"#), json!({ "attributes": [{ "trait_type": "hat", "value": "red" }] })),
+                (json!("plain text"), json!("plain text")),
+                (json!({ "level": 3 }), json!({ "level": 3 })),
+                (json!(r#"[1,2]"#), json!([1, 2])),
+            ];
+
+            for (value, expected) in cases {
+                assert_eq!(parse_extra(&value), expected, "{}", value);
+            }
+        }
+
+        #[test]
+        fn from_json_keeps_unknown_fields_and_reports_malformed_ones() {
+            let metadata = TokenMetadata::from_json(Some(&json!({
+                "title": "Sword",
+                "copies": "10",
+                "media_hash": "short",
+                "expires_at": "2024-01-01T00:00:00Z",
+                "issued_at": "yesterday",
+                "extra": "{\"level\":3}",
+                "season": 2,
+                "description": null,
+            }))).unwrap();
+
+            assert_eq!(metadata.title.as_deref(), Some("Sword"));
+            assert_eq!(metadata.copies, Some(10));
+            assert_eq!(metadata.expires_at, Some(NEW_YEAR_MS));
+            assert_eq!(metadata.extra, Some(json!({ "level": 3 })));
+            assert_eq!(metadata.unknown_fields.get("season"), Some(&json!(2)));
+            assert_eq!(metadata.description, None);
+
+            // Malformed fields are left empty without failing the rest
+            assert_eq!(metadata.media_hash, None);
+            assert_eq!(metadata.issued_at, None);
+
+            let mut failed_fields: Vec<&str> = metadata.parse_errors.iter().map(|parse_error| parse_error.field.as_str()).collect();
+            failed_fields.sort();
+            assert_eq!(failed_fields, vec!["issued_at", "media_hash"]);
+        }
+
+        #[test]
+        fn from_json_ignores_non_objects() {
+            assert!(TokenMetadata::from_json(None).is_none());
+            assert!(TokenMetadata::from_json(Some(&json!(null))).is_none());
+            assert!(TokenMetadata::from_json(Some(&json!("Sword"))).is_none());
+        }
+
+        #[test]
+        fn merge_replaces_set_fields_and_merges_objects() {
+            let metadata = TokenMetadata::from_json(Some(&json!({
+                "title": "Sword",
+                "rarity": "common",
+                "extra": { "level": 1, "hat": "red" },
+                "season": 1,
+            }))).unwrap();
+            let update = TokenMetadata::from_json(Some(&json!({
+                "rarity": "epic",
+                "extra": { "level": 2 },
+                "guild": "north",
+            }))).unwrap();
+
+            let merged = metadata.merge(update);
+
+            assert_eq!(merged.title.as_deref(), Some("Sword"));
+            assert_eq!(merged.rarity.as_deref(), Some("epic"));
+            assert_eq!(merged.extra, Some(json!({ "level": 2, "hat": "red" })));
+            assert_eq!(merged.unknown_fields.get("season"), Some(&json!(1)));
+            assert_eq!(merged.unknown_fields.get("guild"), Some(&json!("north")));
+        }
+
+        #[test]
+        fn merge_keeps_errors_of_untouched_fields() {
+            let metadata = TokenMetadata::from_json(Some(&json!({
+                "copies": "many",
+                "expires_at": "soon",
+                "media_hash": "short",
+            }))).unwrap();
+            let update = TokenMetadata::from_json(Some(&json!({
+                // Fixed
+                "copies": 5,
+                // Still malformed
+                "expires_at": "later",
+                "issued_at": "yesterday",
+            }))).unwrap();
+
+            let merged = metadata.merge(update);
+
+            assert_eq!(merged.copies, Some(5));
+
+            let mut parse_errors: Vec<(&str, &Value)> = merged.parse_errors.iter()
+                .map(|parse_error| (parse_error.field.as_str(), &parse_error.value))
+                .collect();
+            parse_errors.sort_by_key(|(field, _)| *field);
+
+            assert_eq!(parse_errors, vec![
+                ("expires_at", &json!("later")),
+                ("issued_at", &json!("yesterday")),
+                ("media_hash", &json!("short")),
+            ]);
+        }
+    }
+}
@@ -1,5 +1,25 @@
+use near_indexer::near_primitives::types::AccountId;
 use sha3::{ Digest, Keccak256 };
 
+// Version prefix of token database ids
+const TOKEN_DB_ID_VERSION: &str = "v1";
+
 pub(crate) fn keccak256_hash_string(from: String) -> String {
     format!("{:x}", Keccak256::digest(from.as_bytes()))
-}
\ No newline at end of file
+}
+
+/// Database id of a token, `v1:{contract_id}:{token_id}`. Account ids can't contain `:`,
+/// so the contract id ends at the second `:` and the token id is everything after it
+pub(crate) fn get_token_db_id(contract_id: &AccountId, token_id: &str) -> String {
+    format!("{}:{}:{}", TOKEN_DB_ID_VERSION, contract_id, token_id)
+}
+
+/// Id of tokens indexed before versioned ids. The preimage has no separator, so it can be shared by
+/// different tokens. Kept as `legacy_id` on tokens, see `db_adapters::tokens::migrate_token_ids`
+pub(crate) fn get_legacy_token_db_id(contract_id: &AccountId, token_id: &str) -> String {
+    keccak256_hash_string(format!("{}{}", contract_id, token_id))
+}
+
+pub(crate) fn is_token_db_id(id: &str) -> bool {
+    id.starts_with(&format!("{}:", TOKEN_DB_ID_VERSION))
+}